@@ -0,0 +1,51 @@
+#![feature(test)]
+#![allow(non_snake_case)]
+
+extern crate test;
+
+use image::{GrayImage, Luma};
+use rusciii::{
+    asciiGenerator::AsciiGenerator,
+    imageHandler::{ImageHandler, CELL_HEIGHT, CELL_WIDTH},
+};
+use test::{black_box, Bencher};
+
+// A 4K frame, the size of our batch jobs
+fn frame() -> GrayImage {
+    GrayImage::from_fn(3840, 2160, |x, y| Luma([((x ^ y) % 256) as u8]))
+}
+
+fn cells() -> GrayImage {
+    GrayImage::from_fn(3840 / CELL_WIDTH, 2160 / CELL_HEIGHT, |x, y| {
+        Luma([((x * 3 + y * 5) % 256) as u8])
+    })
+}
+
+#[bench]
+fn downsample_naive(b: &mut Bencher) {
+    let img = frame();
+    b.iter(|| black_box(img.clone()).downsampleNaive());
+}
+
+#[bench]
+fn downsample_integral(b: &mut Bencher) {
+    let img = frame();
+    b.iter(|| black_box(img.clone()).downsample());
+}
+
+// The flat glyphs plus building the art's cells from them, against convert_flat alone
+#[bench]
+fn convert_art(b: &mut Bencher) {
+    let ascGen = AsciiGenerator::new();
+    let ramp = ascGen.fontRamp("menlo", "@#MBHA&Gh93X25Sisr;:,. ");
+    let img = cells();
+    b.iter(|| ascGen.convert(&ramp, black_box(&img)));
+}
+
+#[bench]
+fn convert_flat(b: &mut Bencher) {
+    let ascGen = AsciiGenerator::new();
+    let ramp = ascGen.fontRamp("menlo", "@#MBHA&Gh93X25Sisr;:,. ");
+    let img = cells();
+    b.iter(|| ascGen.convertFlat(&ramp, black_box(&img)));
+}
@@ -0,0 +1,471 @@
+use image::{
+    imageops::{
+        blur,
+        colorops::{brighten_in_place, contrast_in_place},
+        crop_imm, flip_horizontal_in_place, flip_vertical_in_place, rotate180, rotate270, rotate90,
+        unsharpen,
+    },
+    GrayImage, Luma,
+};
+use serde::{Deserialize, Serialize};
+
+/// A single image adjustment, run on the resized image before it is downsampled into cells.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Adjustment {
+    Contrast {
+        amount: f32,
+    },
+    Brightness {
+        amount: i32,
+    },
+    /// Values above 1 lift the midtones
+    Gamma {
+        gamma: f32,
+    },
+    /// Scales intensity by 2^stops
+    Exposure {
+        stops: f32,
+    },
+    UnsharpMask {
+        sigma: f32,
+        threshold: i32,
+    },
+    Blur {
+        sigma: f32,
+    },
+    /// Replaces each pixel with the median of its neighbourhood, removing specks
+    Median {
+        radius: u32,
+    },
+    Posterize {
+        levels: u8,
+    },
+    Threshold {
+        level: u8,
+    },
+    /// Keeps a region of the source image, given as fractions of its size
+    Crop {
+        left: f32,
+        top: f32,
+        width: f32,
+        height: f32,
+    },
+    /// Rotates clockwise by 90° per turn
+    Rotate {
+        quarterTurns: u8,
+    },
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl Adjustment {
+    fn all() -> [Adjustment; 12] {
+        [
+            Adjustment::Contrast { amount: 0.0 },
+            Adjustment::Brightness { amount: 0 },
+            Adjustment::Gamma { gamma: 1.0 },
+            Adjustment::Exposure { stops: 0.0 },
+            Adjustment::UnsharpMask {
+                sigma: 1.0,
+                threshold: 2,
+            },
+            Adjustment::Blur { sigma: 1.0 },
+            Adjustment::Median { radius: 1 },
+            Adjustment::Posterize { levels: 4 },
+            Adjustment::Threshold { level: 128 },
+            Adjustment::Rotate { quarterTurns: 1 },
+            Adjustment::FlipHorizontal,
+            Adjustment::FlipVertical,
+        ]
+    }
+
+    pub fn options() -> Vec<(String, String)> {
+        Adjustment::all()
+            .iter()
+            .map(|a| (a.id().to_string(), a.label().to_string()))
+            .collect()
+    }
+
+    pub fn fromId(id: &str) -> Option<Self> {
+        Adjustment::all().into_iter().find(|a| a.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Adjustment::Contrast { .. } => "contrast",
+            Adjustment::Brightness { .. } => "brightness",
+            Adjustment::Gamma { .. } => "gamma",
+            Adjustment::Exposure { .. } => "exposure",
+            Adjustment::UnsharpMask { .. } => "unsharpMask",
+            Adjustment::Blur { .. } => "blur",
+            Adjustment::Median { .. } => "median",
+            Adjustment::Posterize { .. } => "posterize",
+            Adjustment::Threshold { .. } => "threshold",
+            Adjustment::Crop { .. } => "crop",
+            Adjustment::Rotate { .. } => "rotate",
+            Adjustment::FlipHorizontal => "flipHorizontal",
+            Adjustment::FlipVertical => "flipVertical",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Adjustment::Contrast { .. } => "Contrast",
+            Adjustment::Brightness { .. } => "Brightness",
+            Adjustment::Gamma { .. } => "Gamma",
+            Adjustment::Exposure { .. } => "Exposure",
+            Adjustment::UnsharpMask { .. } => "Sharpen",
+            Adjustment::Blur { .. } => "Blur",
+            Adjustment::Median { .. } => "Denoise",
+            Adjustment::Posterize { .. } => "Posterize",
+            Adjustment::Threshold { .. } => "Threshold",
+            Adjustment::Crop { .. } => "Crop",
+            Adjustment::Rotate { .. } => "Rotate",
+            Adjustment::FlipHorizontal => "Flip horizontal",
+            Adjustment::FlipVertical => "Flip vertical",
+        }
+    }
+
+    pub fn isGeometric(&self) -> bool {
+        matches!(
+            self,
+            Adjustment::Crop { .. }
+                | Adjustment::Rotate { .. }
+                | Adjustment::FlipHorizontal
+                | Adjustment::FlipVertical
+        )
+    }
+
+    pub fn hasSlider(&self) -> bool {
+        !matches!(
+            self,
+            Adjustment::Crop { .. } | Adjustment::FlipHorizontal | Adjustment::FlipVertical
+        )
+    }
+
+    pub fn sliderRange(&self) -> (i16, i16) {
+        match self {
+            Adjustment::Contrast { .. } | Adjustment::Brightness { .. } => (-100, 100),
+            Adjustment::Gamma { .. } => (10, 300),
+            Adjustment::Exposure { .. } => (-300, 300),
+            Adjustment::UnsharpMask { .. } | Adjustment::Blur { .. } => (1, 100),
+            Adjustment::Median { .. } => (1, 5),
+            Adjustment::Posterize { .. } => (2, 16),
+            Adjustment::Threshold { .. } => (0, 255),
+            Adjustment::Rotate { .. } => (1, 3),
+            Adjustment::Crop { .. } | Adjustment::FlipHorizontal | Adjustment::FlipVertical => {
+                (0, 0)
+            }
+        }
+    }
+
+    pub fn sliderValue(&self) -> i16 {
+        // Fractional parameters are scaled so the integer slider has enough steps
+        match *self {
+            Adjustment::Contrast { amount } => amount.round() as i16,
+            Adjustment::Brightness { amount } => amount as i16,
+            Adjustment::Gamma { gamma } => (gamma * 100.0).round() as i16,
+            Adjustment::Exposure { stops } => (stops * 100.0).round() as i16,
+            Adjustment::UnsharpMask { sigma, .. } | Adjustment::Blur { sigma } => {
+                (sigma * 10.0).round() as i16
+            }
+            Adjustment::Median { radius } => radius as i16,
+            Adjustment::Posterize { levels } => levels as i16,
+            Adjustment::Threshold { level } => level as i16,
+            Adjustment::Rotate { quarterTurns } => quarterTurns as i16,
+            Adjustment::Crop { .. } | Adjustment::FlipHorizontal | Adjustment::FlipVertical => 0,
+        }
+    }
+
+    pub fn withSliderValue(self, value: i16) -> Self {
+        let (min, max) = self.sliderRange();
+        let v = value.clamp(min, max);
+        match self {
+            Adjustment::Contrast { .. } => Adjustment::Contrast { amount: v as f32 },
+            Adjustment::Brightness { .. } => Adjustment::Brightness { amount: v as i32 },
+            Adjustment::Gamma { .. } => Adjustment::Gamma {
+                gamma: v as f32 / 100.0,
+            },
+            Adjustment::Exposure { .. } => Adjustment::Exposure {
+                stops: v as f32 / 100.0,
+            },
+            Adjustment::UnsharpMask { threshold, .. } => Adjustment::UnsharpMask {
+                sigma: v as f32 / 10.0,
+                threshold,
+            },
+            Adjustment::Blur { .. } => Adjustment::Blur {
+                sigma: v as f32 / 10.0,
+            },
+            Adjustment::Median { .. } => Adjustment::Median { radius: v as u32 },
+            Adjustment::Posterize { .. } => Adjustment::Posterize { levels: v as u8 },
+            Adjustment::Threshold { .. } => Adjustment::Threshold { level: v as u8 },
+            Adjustment::Rotate { .. } => Adjustment::Rotate {
+                quarterTurns: v as u8,
+            },
+            Adjustment::Crop { .. } | Adjustment::FlipHorizontal | Adjustment::FlipVertical => self,
+        }
+    }
+
+    pub fn clamped(self) -> Self {
+        // Values from outside the sliders can be big enough to hang the worker
+        match self {
+            Adjustment::Crop {
+                left,
+                top,
+                width,
+                height,
+            } => Adjustment::Crop {
+                left: left.clamp(0.0, 1.0),
+                top: top.clamp(0.0, 1.0),
+                width: width.clamp(0.0, 1.0),
+                height: height.clamp(0.0, 1.0),
+            },
+            // Any number of turns is cheap, and 0 or 4 mean no turn rather than one
+            Adjustment::Rotate { .. } | Adjustment::FlipHorizontal | Adjustment::FlipVertical => {
+                self
+            }
+            _ => self.withSliderValue(self.sliderValue()),
+        }
+    }
+
+    pub fn apply(&self, mut img: GrayImage) -> GrayImage {
+        match *self {
+            Adjustment::Contrast { amount } => {
+                contrast_in_place(&mut img, amount);
+                img
+            }
+            Adjustment::Brightness { amount } => {
+                brighten_in_place(&mut img, amount);
+                img
+            }
+            Adjustment::Gamma { gamma } => {
+                let inv = 1.0 / gamma.max(0.01);
+                mapPixels(img, |v| {
+                    ((v as f32 / 255.0).powf(inv) * 255.0).round() as u8
+                })
+            }
+            Adjustment::Exposure { stops } => {
+                let scale = stops.exp2();
+                mapPixels(img, |v| (v as f32 * scale).round().min(255.0) as u8)
+            }
+            Adjustment::UnsharpMask { sigma, threshold } => unsharpen(&img, sigma, threshold),
+            Adjustment::Blur { sigma } => blur(&img, sigma),
+            Adjustment::Median { radius } => median(&img, radius),
+            Adjustment::Posterize { levels } => {
+                let steps = (levels.max(2) - 1) as f32;
+                mapPixels(img, |v| {
+                    ((v as f32 * steps / 255.0).round() * 255.0 / steps).round() as u8
+                })
+            }
+            Adjustment::Threshold { level } => mapPixels(img, |v| if v >= level { 255 } else { 0 }),
+            Adjustment::Crop {
+                left,
+                top,
+                width,
+                height,
+            } => {
+                let (w, h) = img.dimensions();
+                let x = ((left * w as f32).round() as u32).min(w.saturating_sub(1));
+                let y = ((top * h as f32).round() as u32).min(h.saturating_sub(1));
+                let cw = ((width * w as f32).round() as u32).clamp(1, w - x);
+                let ch = ((height * h as f32).round() as u32).clamp(1, h - y);
+                crop_imm(&img, x, y, cw, ch).to_image()
+            }
+            Adjustment::Rotate { quarterTurns } => match quarterTurns % 4 {
+                1 => rotate90(&img),
+                2 => rotate180(&img),
+                3 => rotate270(&img),
+                _ => img,
+            },
+            Adjustment::FlipHorizontal => {
+                flip_horizontal_in_place(&mut img);
+                img
+            }
+            Adjustment::FlipVertical => {
+                flip_vertical_in_place(&mut img);
+                img
+            }
+        }
+    }
+}
+
+fn mapPixels(mut img: GrayImage, f: impl Fn(u8) -> u8) -> GrayImage {
+    let mut lut = [0_u8; 256];
+    lut.iter_mut()
+        .enumerate()
+        .for_each(|(i, slot)| *slot = f(i as u8));
+    img.iter_mut().for_each(|p| *p = lut[*p as usize]);
+    img
+}
+
+fn median(img: &GrayImage, radius: u32) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let r = radius as i64;
+    let mut window: Vec<u8> = Vec::with_capacity(((2 * r + 1) * (2 * r + 1)) as usize);
+    GrayImage::from_fn(w, h, |x, y| {
+        window.clear();
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let nx = (x as i64 + dx).clamp(0, w as i64 - 1) as u32;
+                let ny = (y as i64 + dy).clamp(0, h as i64 - 1) as u32;
+                window.push(img.get_pixel(nx, ny)[0]);
+            }
+        }
+        let mid = window.len() / 2;
+        Luma([*window.select_nth_unstable(mid).1])
+    })
+}
+
+/// Ordered list of adjustments. Serializes to JSON so a look can be shared.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub steps: Vec<Adjustment>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline {
+            steps: vec![
+                Adjustment::Contrast { amount: 0.0 },
+                Adjustment::Brightness { amount: 0 },
+            ],
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn apply(&self, img: GrayImage) -> GrayImage {
+        self.steps.iter().fold(img, |img, step| step.apply(img))
+    }
+
+    pub fn applyGeometry(&self, img: GrayImage) -> GrayImage {
+        self.steps
+            .iter()
+            .filter(|s| s.isGeometric())
+            .fold(img, |img, step| step.apply(img))
+    }
+
+    pub fn applyFilters(&self, img: GrayImage) -> GrayImage {
+        self.steps
+            .iter()
+            .filter(|s| !s.isGeometric())
+            .fold(img, |img, step| step.apply(img))
+    }
+
+    pub fn geometry(&self) -> Vec<Adjustment> {
+        self.steps
+            .iter()
+            .filter(|s| s.isGeometric())
+            .copied()
+            .collect()
+    }
+
+    pub fn filters(&self) -> Vec<Adjustment> {
+        self.steps
+            .iter()
+            .filter(|s| !s.isGeometric())
+            .copied()
+            .collect()
+    }
+
+    pub fn crop(&self) -> Option<Adjustment> {
+        self.steps
+            .iter()
+            .find(|s| matches!(s, Adjustment::Crop { .. }))
+            .copied()
+    }
+
+    pub fn setCrop(&mut self, crop: Option<Adjustment>) {
+        // The crop box is drawn over the source image, so it always runs first
+        self.steps.retain(|s| !matches!(s, Adjustment::Crop { .. }));
+        if let Some(crop) = crop {
+            self.steps.insert(0, crop);
+        }
+    }
+
+    pub fn moveStep(&mut self, index: usize, up: bool) {
+        let target = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1).filter(|&i| i < self.steps.len())
+        };
+        if let Some(target) = target {
+            self.steps.swap(index, target);
+        }
+    }
+
+    pub fn toJson(&self) -> String {
+        serde_json::to_string(self).expect("Pipeline should serialize")
+    }
+
+    pub fn fromJson(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slider_value_round_trips() {
+        for adj in Adjustment::all() {
+            let (min, max) = adj.sliderRange();
+            for v in [min, max] {
+                assert_eq!(adj.withSliderValue(v).sliderValue(), v, "{}", adj.id());
+            }
+        }
+    }
+
+    #[test]
+    fn test_posterize_and_threshold() {
+        let img = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        let posterized = Adjustment::Posterize { levels: 3 }.apply(img.clone());
+        let mut levels: Vec<u8> = posterized.iter().copied().collect();
+        levels.dedup();
+        assert_eq!(levels, vec![0, 128, 255]);
+        let bw = Adjustment::Threshold { level: 100 }.apply(img);
+        assert_eq!((bw.get_pixel(99, 0)[0], bw.get_pixel(100, 0)[0]), (0, 255));
+    }
+
+    #[test]
+    fn test_median_removes_speck() {
+        let mut img = GrayImage::from_pixel(5, 5, Luma([200]));
+        img.put_pixel(2, 2, Luma([0]));
+        let out = Adjustment::Median { radius: 1 }.apply(img);
+        assert!(out.iter().all(|&p| p == 200));
+    }
+
+    #[test]
+    fn test_geometry_runs_separately() {
+        let img = GrayImage::from_fn(4, 2, |x, y| Luma([(y * 4 + x) as u8 * 10]));
+        let mut pipeline = Pipeline::default();
+        pipeline.steps.push(Adjustment::Rotate { quarterTurns: 1 });
+        pipeline.steps.push(Adjustment::FlipVertical);
+        pipeline.setCrop(Some(Adjustment::Crop {
+            left: 0.5,
+            top: 0.0,
+            width: 0.5,
+            height: 1.0,
+        }));
+        assert_eq!(pipeline.steps[0].id(), "crop");
+        let out = pipeline.applyGeometry(img);
+        // Right half [20 30; 60 70] rotated clockwise then flipped upside down
+        assert_eq!(out.dimensions(), (2, 2));
+        assert_eq!(out.into_raw(), vec![70, 30, 60, 20]);
+        pipeline.setCrop(None);
+        assert_eq!(pipeline.crop(), None);
+    }
+
+    #[test]
+    fn test_pipeline_json_round_trip() {
+        let mut pipeline = Pipeline::default();
+        pipeline.steps.push(Adjustment::Gamma { gamma: 1.8 });
+        pipeline.moveStep(2, true);
+        assert_eq!(pipeline.steps[1], Adjustment::Gamma { gamma: 1.8 });
+        let json = pipeline.toJson();
+        assert!(json.contains(r#""type":"gamma""#));
+        assert_eq!(Pipeline::fromJson(&json).unwrap(), pipeline);
+    }
+}
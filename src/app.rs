@@ -1,14 +1,22 @@
-use image::{
-    imageops::{self, brighten},
-    DynamicImage, GrayImage,
-};
-use leptos::*;
+use image::DynamicImage;
+use leptos::{leptos_dom::helpers::TimeoutHandle, *};
+use std::time::Duration;
 
 use crate::{
-    imageHandler::ImageHandler,
+    adjustments::Pipeline,
+    asciiArt::AsciiArt,
+    asciiGenerator::AsciiGenerator,
+    composition::{Composition, LayerKind},
+    imageHandler::{alphaMask, composite, OutputSize, Tone, Transparency},
+    ramp::{Background, RampSource},
     sections::{artpanel::*, sidebar::*},
+    session,
+    settings::{readUrlSettings, writeUrlSettings, ConversionSettings},
+    worker::{ConversionWorker, Rendered, WorkerFailure},
 };
 
+const DEBOUNCE_MS: u64 = 80;
+
 #[derive(Clone)]
 pub struct Test {
     n: i32,
@@ -22,27 +30,175 @@ impl Test {
 
 #[component]
 pub fn App() -> impl IntoView {
+    let ascGen = store_value(AsciiGenerator::new());
+    provide_context(ascGen);
     let (image, setImage) = create_signal::<Option<DynamicImage>>(None);
-    let (gray, setGray) = create_signal::<Option<GrayImage>>(None);
-    let (contrast, setContrast) = create_signal::<f32>(0.0);
-    let (brightness, setBrightness) = create_signal::<i32>(0);
-    let (size, setSize) = create_signal::<u16>(100);
-    let (resizedImage, setResizedImage) = create_signal::<Option<GrayImage>>(None);
-    let (dither, setDither) = create_signal::<bool>(false);
-    let filteredImage = move || {
-        with!(
-            |resizedImage, contrast, brightness| resizedImage.as_ref().map(|img| brighten(
-                &imageops::contrast(img, *contrast),
-                *brightness
-            )
-            .stretchContrast())
-        )
+    let (notice, setNotice) = create_signal::<Option<String>>(None);
+    // A shared link carries its settings and wins over the ones from last session
+    let initial = match ascGen.with_value(readUrlSettings) {
+        Some(Ok(settings)) => settings,
+        Some(Err(err)) => {
+            logging::warn!("Ignoring settings in url: {err}");
+            setNotice(Some(format!(
+                "The link's settings can't be used ({err}), using defaults"
+            )));
+            ConversionSettings::default()
+        }
+        None => ascGen.with_value(session::loadSettings).unwrap_or_default(),
     };
+    let (size, setSize) = create_signal(initial.size);
+    let (pipeline, setPipeline) = create_signal(initial.pipeline);
+    let (dither, setDither) = create_signal(initial.dither);
+    let (tone, setTone) = create_signal(initial.tone);
+    let (font, setFont) = create_signal(initial.font);
+    let (rampSource, setRampSource) = create_signal(initial.rampSource);
+    let (background, setBackground) = create_signal(initial.background);
+    let (transparency, setTransparency) = create_signal(initial.transparency);
+    let settings = create_memo(move |_| ConversionSettings {
+        font: font(),
+        rampSource: rampSource(),
+        size: size(),
+        pipeline: pipeline(),
+        tone: tone(),
+        dither: dither(),
+        background: background(),
+        transparency: transparency(),
+    });
+    let applySettings = Callback::new(move |s: ConversionSettings| {
+        logging::log!("Applying settings...");
+        batch(|| {
+            setFont(s.font);
+            setRampSource(s.rampSource);
+            setSize(s.size);
+            setPipeline(s.pipeline);
+            setTone(s.tone);
+            setDither(s.dither);
+            setBackground(s.background);
+            setTransparency(s.transparency);
+        })
+    });
+    let gray = create_memo(move |_| {
+        // Blank cells show the page, so semi transparent edges blend towards it
+        let color = match transparency() {
+            Transparency::Composite(color) => color,
+            Transparency::Blank => background().color(),
+        };
+        with!(|image| image
+            .as_ref()
+            .map(|img| DynamicImage::ImageRgb8(composite(img, color)).into_luma8()))
+    });
+    let alpha = create_memo(move |_| match transparency() {
+        Transparency::Blank => with!(|image| image.as_ref().and_then(alphaMask)),
+        Transparency::Composite(_) => None,
+    });
+    // Conversion runs in a worker so large images don't freeze the page
+    let (rendered, setRendered) = create_signal::<Option<Rendered>>(None);
+    let (restarts, setRestarts) = create_signal(0_u32);
+    let onFailed = move |failure: WorkerFailure| {
+        logging::warn!("{failure}");
+        if let WorkerFailure::Crashed(_) = failure {
+            setRestarts.update(|n| *n += 1);
+        }
+        setNotice(Some(failure.to_string()));
+    };
+    let worker = store_value(ConversionWorker::spawn(
+        move |r| setRendered(Some(r)),
+        onFailed,
+    ));
+    create_effect(move |lastRestart: Option<u32>| {
+        // A restarted worker gets the image back, but not the job that crashed it
+        let restart = restarts();
+        with!(|gray, alpha| worker.with_value(|w| w.setImage(gray.as_ref(), alpha.as_ref())));
+        if gray.with(Option::is_some) {
+            if lastRestart.is_none_or(|last| last == restart) {
+                let job = settings.get_untracked();
+                worker.with_value(|w| w.submit(job));
+            }
+        } else {
+            setRendered(None);
+        }
+        restart
+    });
+    // Slider drags fire on every step, so only the value they settle on is sent
+    let pendingJob = store_value::<Option<TimeoutHandle>>(None);
+    create_effect(move |_| {
+        let job = settings();
+        if let Some(handle) = pendingJob.get_value() {
+            handle.clear();
+        }
+        let handle = set_timeout_with_handle(
+            move || {
+                writeUrlSettings(&job);
+                session::saveSettings(&job);
+                if gray.with_untracked(Option::is_some) {
+                    worker.with_value(|w| w.submit(job));
+                }
+            },
+            Duration::from_millis(DEBOUNCE_MS),
+        )
+        .ok();
+        pendingJob.set_value(handle);
+    });
+    let art = Signal::derive(move || rendered.with(|r| r.as_ref().map(|r| r.art.clone())));
+    // Conversions and the banner text feed their live layers, kept layers stay put
+    let (composition, setComposition) = create_signal(Composition::default());
+    let (selected, setSelected) = create_signal::<Option<u32>>(None);
+    let (banner, setBanner) = create_signal::<Option<AsciiArt>>(None);
+    create_effect(move |_| {
+        let converted = art.with(|a| a.as_deref().map(AsciiArt::fromLines));
+        setComposition.update(|c| c.updateLive(LayerKind::Image, converted));
+    });
+    create_effect(move |_| {
+        let text = banner();
+        setComposition.update(|c| c.updateLive(LayerKind::Banner, text));
+    });
+    let grid = Signal::derive(move || rendered.with(|r| r.as_ref().map(|r| (r.columns, r.rows))));
+    let timings = Signal::derive(move || {
+        rendered.with(|r| r.as_ref().map(|r| r.timings.clone()).unwrap_or_default())
+    });
 
     view! {
-        <div class="w-full h-full flex flex-row divide-x divide-amber-500">
-            <Sidebar setImage gray setGray setResizedImage setContrast setBrightness setDither/>
-            <ArtPanel image=filteredImage dither=dither/>
+        <div class="relative w-full h-full flex flex-row divide-x divide-amber-500">
+            <Show when=move || notice.with(Option::is_some)>
+                <div class="absolute z-10 top-4 left-1/2 -translate-x-1/2 p-2 flex flex-row space-x-4 rounded-sm border border-red-600 bg-amber-50 text-sm text-red-600">
+                    <span>{notice}</span>
+                    <button title="Dismiss" on:click=move |_| setNotice(None)>"✕"</button>
+                </div>
+            </Show>
+            <Sidebar
+                settings
+                applySettings
+                setImage
+                transparency
+                setTransparency
+                size
+                setSize
+                grid
+                pipeline
+                setPipeline
+                dither
+                setDither
+                tone
+                setTone
+                font
+                setFont
+                rampSource
+                setRampSource
+                setBanner
+                composition
+                setComposition
+                selected
+                setSelected
+            />
+            <ArtPanel
+                composition
+                setComposition
+                selected
+                timings
+                font
+                background
+                setBackground
+            />
         </div>
     }
 }
@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Text attributes of a cell, combined as bit flags.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Attributes(u8);
+
+impl Attributes {
+    pub const NONE: Self = Self(0);
+    pub const BOLD: Self = Self(1);
+    pub const ITALIC: Self = Self(1 << 1);
+    pub const UNDERLINE: Self = Self(1 << 2);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn with(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn sgr(&self) -> impl Iterator<Item = u8> + '_ {
+        // SGR parameters switching the attributes on
+        [(Self::BOLD, 1), (Self::ITALIC, 3), (Self::UNDERLINE, 4)]
+            .into_iter()
+            .filter(|(a, _)| self.contains(*a))
+            .map(|(_, code)| code)
+    }
+}
+
+/// One char of the art. Cells without a color take whatever the renderer draws on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Option<[u8; 3]>,
+    pub bg: Option<[u8; 3]>,
+    pub attrs: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::from(' ')
+    }
+}
+
+impl From<char> for Cell {
+    fn from(glyph: char) -> Self {
+        Self {
+            glyph,
+            fg: None,
+            bg: None,
+            attrs: Attributes::NONE,
+        }
+    }
+}
+
+impl Cell {
+    pub fn clear(&mut self) {
+        *self = Cell::default();
+    }
+
+    pub fn isStyled(&self) -> bool {
+        self.fg.is_some() || self.bg.is_some() || self.attrs != Attributes::NONE
+    }
+}
+
+/// Grid of cells in row major order, shared by the converter, exporters and renderers.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AsciiArt {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl AsciiArt {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width * height],
+        }
+    }
+
+    pub fn fromGlyphs(width: usize, glyphs: Vec<char>) -> Self {
+        let height = glyphs.len().checked_div(width).unwrap_or(0);
+        assert_eq!(
+            width * height,
+            glyphs.len(),
+            "Glyphs should fill whole rows"
+        );
+        Self {
+            width,
+            height,
+            cells: glyphs.into_iter().map(Cell::from).collect(),
+        }
+    }
+
+    pub fn fromLines(text: &str) -> Self {
+        // Shorter lines are padded with blank cells
+        let lines: Vec<&str> = text.lines().collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut art = Self::new(width, lines.len());
+        for (row, line) in art.rowsMut().zip(lines) {
+            row.iter_mut()
+                .zip(line.chars())
+                .for_each(|(cell, c)| cell.glyph = c);
+        }
+        art
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn isEmpty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
+        (x < self.width && y < self.height).then(|| &self.cells[y * self.width + x])
+    }
+
+    pub fn getMut(&mut self, x: usize, y: usize) -> Option<&mut Cell> {
+        (x < self.width && y < self.height).then(|| &mut self.cells[y * self.width + x])
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn cellsMut(&mut self) -> &mut [Cell] {
+        &mut self.cells
+    }
+
+    pub fn row(&self, y: usize) -> &[Cell] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks_exact(self.width.max(1))
+    }
+
+    pub fn rowsMut(&mut self) -> impl Iterator<Item = &mut [Cell]> {
+        self.cells.chunks_exact_mut(self.width.max(1))
+    }
+
+    pub fn glyphs(&self) -> impl Iterator<Item = char> + '_ {
+        self.cells.iter().map(|c| c.glyph)
+    }
+
+    pub fn enumerate(&self) -> impl Iterator<Item = (usize, usize, &Cell)> {
+        let width = self.width.max(1);
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, c)| (i % width, i / width, c))
+    }
+
+    pub fn slice(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        // Clamped to the grid, so a region hanging off the edge comes back smaller
+        let x0 = x.min(self.width);
+        let y0 = y.min(self.height);
+        let x1 = x.saturating_add(width).min(self.width);
+        let y1 = y.saturating_add(height).min(self.height);
+        let cells = (y0..y1)
+            .flat_map(|row| self.row(row)[x0..x1].iter().copied())
+            .collect();
+        Self {
+            width: x1 - x0,
+            height: y1 - y0,
+            cells,
+        }
+    }
+
+    pub fn stamp(&mut self, other: &AsciiArt, x: usize, y: usize) {
+        // Blank cells of the stamped art let this art show through, the rest is clipped to the grid
+        for (ox, oy, cell) in other.enumerate() {
+            if cell.glyph == ' ' && !cell.isStyled() {
+                continue;
+            }
+            if let Some(target) = self.getMut(x + ox, y + oy) {
+                *target = *cell;
+            }
+        }
+    }
+
+    pub fn paste(&mut self, other: &AsciiArt, x: usize, y: usize) {
+        // Like stamp, but blank cells cover what's underneath too
+        for (ox, oy, cell) in other.enumerate() {
+            if let Some(target) = self.getMut(x + ox, y + oy) {
+                *target = *cell;
+            }
+        }
+    }
+
+    pub fn toAnsi(&self) -> String {
+        // Escapes only where the style changes, reset at the end of every line
+        let mut out = String::with_capacity(self.cells.len() * 2);
+        for (y, row) in self.rows().enumerate() {
+            if y > 0 {
+                out.push('\n');
+            }
+            let mut current = Cell::default();
+            for cell in row {
+                if (cell.fg, cell.bg, cell.attrs) != (current.fg, current.bg, current.attrs) {
+                    out.push_str(&sgr(cell));
+                    current = *cell;
+                }
+                out.push(cell.glyph);
+            }
+            if current.isStyled() {
+                out.push_str("\x1B[0m");
+            }
+        }
+        out
+    }
+}
+
+fn sgr(cell: &Cell) -> String {
+    let mut codes = vec!["0".to_string()];
+    codes.extend(cell.attrs.sgr().map(|c| c.to_string()));
+    if let Some([r, g, b]) = cell.fg {
+        codes.push(format!("38;2;{r};{g};{b}"));
+    }
+    if let Some([r, g, b]) = cell.bg {
+        codes.push(format!("48;2;{r};{g};{b}"));
+    }
+    format!("\x1B[{}m", codes.join(";"))
+}
+
+impl fmt::Display for AsciiArt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (y, row) in self.rows().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for cell in row {
+                write!(f, "{}", cell.glyph)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::Index<(usize, usize)> for AsciiArt {
+    type Output = Cell;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Cell {
+        self.get(x, y).expect("Cell should be inside the art")
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for AsciiArt {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Cell {
+        self.getMut(x, y).expect("Cell should be inside the art")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_round_trip() {
+        let art = AsciiArt::fromLines("@#\n.");
+        assert_eq!((art.width(), art.height()), (2, 2));
+        assert_eq!(art.to_string(), "@#\n. ");
+        assert_eq!(art[(1, 0)].glyph, '#');
+        assert_eq!(art.get(2, 0), None);
+        assert_eq!(AsciiArt::default().to_string(), "");
+    }
+
+    #[test]
+    fn test_slice_clamps_to_grid() {
+        let art = AsciiArt::fromGlyphs(3, "abcdefghi".chars().collect());
+        assert_eq!(art.slice(1, 1, 5, 5).to_string(), "ef\nhi");
+        assert!(art.slice(4, 0, 2, 2).isEmpty());
+    }
+
+    #[test]
+    fn test_stamp_skips_blanks_and_clips() {
+        let mut art = AsciiArt::fromLines("....\n....");
+        art.stamp(&AsciiArt::fromLines("a b\ncde"), 2, 1);
+        assert_eq!(art.to_string(), "....\n..a.");
+    }
+
+    #[test]
+    fn test_ansi_only_escapes_style_changes() {
+        let mut art = AsciiArt::fromLines("abc");
+        art[(0, 0)].fg = Some([255, 0, 0]);
+        art[(1, 0)].fg = Some([255, 0, 0]);
+        art[(2, 0)].attrs = Attributes::BOLD.with(Attributes::UNDERLINE);
+        assert_eq!(art.toAnsi(), "\x1B[0;38;2;255;0;0mab\x1B[0;1;4mc\x1B[0m");
+        assert_eq!(AsciiArt::fromLines("ab").toAnsi(), "ab");
+    }
+}
@@ -2,6 +2,8 @@ use clipboard::{ClipboardContext, ClipboardProvider};
 use image::{imageops::dither, GenericImageView, GrayImage, RgbImage};
 use leptos::svg::image;
 use leptos::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
@@ -10,7 +12,12 @@ use std::{
     path::PathBuf,
 };
 
-use crate::utils::AsciiColorMap;
+use crate::{
+    asciiArt::AsciiArt,
+    imageHandler::TRANSPARENT_BELOW,
+    ramp::{loadPresets, CharsetError, Ramp, RampPreset, RampSource, Spacing},
+    utils::AsciiColorMap,
+};
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
 struct Char {
@@ -22,24 +29,51 @@ struct Char {
 #[derive(Serialize, Deserialize)]
 struct Font {
     name: String,
+    family: String,
     charIntensity: Vec<u8>,
     charDeviation: Vec<u8>,
 }
 
+// Intensity error a char may trade for one unit less deviation when optimizing a charset
+const DEVIATION_WEIGHT: f32 = 0.1;
+
 pub struct AsciiGenerator {
     fonts: Vec<Font>,
+    presets: Vec<RampPreset>,
 }
 
 impl AsciiGenerator {
     pub fn new() -> Self {
         let jsonData = include_str!("../data/fonts.json");
         let fonts: Vec<Font> = serde_json::from_str(jsonData).expect("Failed to parse fonts json");
-        Self { fonts }
+        let presets = loadPresets();
+        Self { fonts, presets }
+    }
+
+    pub fn fontNames(&self) -> Vec<(String, String)> {
+        // Font ids paired with the web font family their metrics were measured in
+        self.fonts
+            .iter()
+            .map(|f| (f.name.clone(), f.family.clone()))
+            .collect()
+    }
+
+    pub fn fontFamily(&self, font: &str) -> String {
+        self.fonts
+            .iter()
+            .find(|f| f.name == font)
+            .map(|f| f.family.clone())
+            .unwrap_or_else(|| panic!("Font {font} not found"))
+    }
+
+    pub fn presets(&self) -> &[RampPreset] {
+        &self.presets
     }
 
     fn getChars(&self, font: &str) -> Vec<Char> {
         let Font {
             name: _,
+            family: _,
             charIntensity: ci,
             charDeviation: ca,
         } = self
@@ -74,142 +108,188 @@ impl AsciiGenerator {
         });
     }
 
-    fn scaleIntensity(intensy: u8, minI: u8, rangeI: u8) -> u8 {
-        ((intensy - minI) as f32 * 255.0 / rangeI as f32).round() as u8
-    }
-
-    pub fn getIntensityDistAndCharMap(
-        &self,
-        font: &str,
-        chosenChars: &str,
-    ) -> ([u8; 256], [Option<u8>; 256]) {
+    pub fn fontRamp(&self, font: &str, chosenChars: &str) -> Ramp {
+        // Places the chosen chars on the ramp by their measured intensity in the font
         let mut chars = self.getChars(font);
         chars.retain(|c| {
             let char = char::from(c.id);
             chosenChars.contains(char)
         });
         self.elimDupIntensyChars(&mut chars);
+        self.normalizeCharIntsy(&mut chars);
+        Ramp::fromStops(chars.iter().map(|c| (char::from(c.id), c.intensity)))
+    }
 
-        // Scale intensity to 0-255
-        let (minI, maxI) = (chars[0].intensity, chars[chars.len() - 1].intensity);
-        let rangeI = maxI - minI;
-        let scaledIntensity: Vec<(u8, u8)> = chars
-            .iter()
-            .map(|c| (Self::scaleIntensity(c.intensity, minI, rangeI), c.id))
-            .collect();
-
-        // Intensity to char map
-        let mut intensityToChar: [Option<u8>; 256] = [None; 256];
-        for (i, c) in scaledIntensity.iter() {
-            intensityToChar[*i as usize] = Some(*c);
+    pub fn ramp(&self, font: &str, source: &RampSource) -> Ramp {
+        match source {
+            RampSource::Preset(id) => {
+                let preset = self
+                    .presets
+                    .iter()
+                    .find(|p| &p.id == id)
+                    .unwrap_or_else(|| panic!("Ramp preset {id} not found"));
+                match preset.spacing {
+                    Spacing::Even => Ramp::fromCharset(&preset.chars),
+                    Spacing::Font => self.fontRamp(font, &preset.chars),
+                }
+            }
+            RampSource::Custom(chars) => self.fontRamp(font, chars),
         }
+    }
 
-        let mut intensityDist: [u8; 256] = [0; 256];
-        let mut cur: usize = 1;
-        for (i, distSlot) in intensityDist.iter_mut().enumerate() {
-            let gray = i as u8;
-            if gray > scaledIntensity[cur].0 {
-                cur += 1;
-            }
-            *distSlot = if (gray - scaledIntensity[cur - 1].0 < scaledIntensity[cur].0 - gray) {
-                scaledIntensity[cur - 1].0
-            } else {
-                scaledIntensity[cur].0
-            }
+    pub fn validateCharset(&self, font: &str, chosenChars: &str) -> Result<(), CharsetError> {
+        if chosenChars.chars().count() < 2 {
+            return Err(CharsetError::TooShort);
         }
-        (intensityDist, intensityToChar)
+        let mut unsupported: Vec<char> = chosenChars
+            .chars()
+            .filter(|c| !(' '..='~').contains(c))
+            .collect();
+        if !unsupported.is_empty() {
+            unsupported.sort();
+            unsupported.dedup();
+            return Err(CharsetError::Unsupported(unsupported.into_iter().collect()));
+        }
+        let levels: HashSet<u8> = self
+            .getChars(font)
+            .iter()
+            .filter(|c| chosenChars.contains(char::from(c.id)))
+            .map(|c| c.intensity)
+            .collect();
+        if levels.len() < 2 {
+            return Err(CharsetError::TooFewLevels);
+        }
+        Ok(())
     }
 
-    fn getWeightedRamp(&self, font: &str, chosenChars: &str) -> [u8; 256] {
+    pub fn optimalCharset(&self, font: &str, len: usize) -> String {
+        // Picks the chars closest to evenly spaced intensity targets, preferring uniform glyphs
         let mut chars = self.getChars(font);
-        chars.retain(|c| {
-            let char = char::from(c.id);
-            chosenChars.contains(char)
-        });
         self.elimDupIntensyChars(&mut chars);
         self.normalizeCharIntsy(&mut chars);
-
-        // Build ascii ramp
-        let mut asciiRamp: [u8; 256] = [chars[0].id; 256];
-        let mut cur: usize = 1;
-        for (i, rampSlot) in asciiRamp.iter_mut().enumerate() {
-            let gray = i as u8;
-            if gray > chars[cur].intensity {
-                cur += 1;
-            }
-            *rampSlot = if (gray - chars[cur - 1].intensity < chars[cur].intensity - gray) {
-                chars[cur - 1].id
-            } else {
-                chars[cur].id
+        let n = len.clamp(2, chars.len());
+        let m = chars.len();
+        let cost = |k: usize, c: &Char| {
+            let target = k as f32 * 255.0 / (n - 1) as f32;
+            (c.intensity as f32 - target).abs() + DEVIATION_WEIGHT * c.deviation as f32
+        };
+
+        // best[k][i]: lowest cost of placing targets 0..=k with target k on char i
+        let mut best = vec![vec![f32::INFINITY; m]; n];
+        let mut prev = vec![vec![0_usize; m]; n];
+        for (i, c) in chars.iter().enumerate() {
+            best[0][i] = cost(0, c);
+        }
+        for k in 1..n {
+            for i in k..m {
+                let (j, base) = (k - 1..i)
+                    .map(|j| (j, best[k - 1][j]))
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("Previous target must have a candidate");
+                best[k][i] = base + cost(k, &chars[i]);
+                prev[k][i] = j;
             }
         }
-        asciiRamp
+
+        let mut i = (0..m)
+            .min_by(|&a, &b| best[n - 1][a].total_cmp(&best[n - 1][b]))
+            .expect("Font must have chars");
+        let mut picked = vec![i];
+        for k in (1..n).rev() {
+            i = prev[k][i];
+            picked.push(i);
+        }
+        picked
+            .iter()
+            .rev()
+            .map(|&i| char::from(chars[i].id))
+            .collect()
     }
 
-    pub fn convert(&self, font: &str, chars: &str, img: &GrayImage) -> Vec<Vec<u8>> {
-        let ramp = self.getWeightedRamp(font, chars);
-        let (w, h) = img.dimensions();
-        let mut asciiArt: Vec<Vec<u8>> = vec![vec![0; w as usize]; h as usize];
-        for (x, y, p) in img.enumerate_pixels() {
-            asciiArt[y as usize][x as usize] = ramp[p[0] as usize];
+    pub fn getIntensityDistAndCharMap(
+        &self,
+        font: &str,
+        chosenChars: &str,
+    ) -> ([u8; 256], [Option<char>; 256]) {
+        let ramp = self.fontRamp(font, chosenChars);
+        let mut intensityToChar: [Option<char>; 256] = [None; 256];
+        for s in ramp.stops() {
+            intensityToChar[s.position as usize] = Some(s.glyph);
         }
-        asciiArt
+        (ramp.levels(), intensityToChar)
     }
 
-    pub fn convertWithDither(&self, font: &str, chars: &str, img: &GrayImage) -> Vec<Vec<u8>> {
-        let (intensityMap, charMap) = self.getIntensityDistAndCharMap(font, chars);
-        let ascColorMap = AsciiColorMap::new(intensityMap);
+    fn getWeightedRamp(&self, font: &str, chosenChars: &str) -> [char; 256] {
+        self.fontRamp(font, chosenChars)
+            .lookupTable(&GrayImage::new(0, 0))
+    }
+
+    pub fn convert(&self, ramp: &Ramp, img: &GrayImage) -> AsciiArt {
+        AsciiArt::fromGlyphs(img.width() as usize, self.convertFlat(ramp, img))
+    }
+
+    pub fn convertFlat(&self, ramp: &Ramp, img: &GrayImage) -> Vec<char> {
+        // Row major glyphs in one allocation
+        let table = ramp.lookupTable(img);
+        #[cfg(feature = "parallel")]
+        let glyphs = img
+            .as_raw()
+            .par_iter()
+            .map(|&p| table[p as usize])
+            .collect();
+        #[cfg(not(feature = "parallel"))]
+        let glyphs = img.as_raw().iter().map(|&p| table[p as usize]).collect();
+        glyphs
+    }
+
+    pub fn convertWithDither(&self, ramp: &Ramp, img: &GrayImage) -> AsciiArt {
+        let curve = ramp.toneCurve(img);
+        let ascColorMap = AsciiColorMap::new(ramp.levels());
         let mut imgClone = img.clone();
+        imgClone.iter_mut().for_each(|p| *p = curve[*p as usize]);
         dither(&mut imgClone, &ascColorMap);
-        let ramp = self.getWeightedRamp(font, chars);
-        let (w, h) = imgClone.dimensions();
-        let mut asciiArt: Vec<Vec<u8>> = vec![vec![0; w as usize]; h as usize];
-        for (x, y, p) in imgClone.enumerate_pixels() {
-            asciiArt[y as usize][x as usize] =
-                charMap[p[0] as usize].expect("Char must exist for provided intensity");
-        }
-        asciiArt
+        let glyphs = imgClone
+            .iter()
+            .map(|&p| {
+                ramp.glyphAt(p)
+                    .expect("Char must exist for provided intensity")
+            })
+            .collect();
+        AsciiArt::fromGlyphs(imgClone.width() as usize, glyphs)
     }
 }
 
-pub fn addAnsiTrueColor(art: &[Vec<u8>], img: &RgbImage) -> Vec<Vec<String>> {
-    let (w, h) = img.dimensions();
-    let (w, h) = (w as usize, h as usize);
-    let mut coloredArt = vec![vec![String::new(); w]; h];
-    for (i, row) in coloredArt.iter_mut().enumerate() {
-        for (j, val) in row.iter_mut().enumerate() {
-            let p = img.get_pixel(j as u32, i as u32);
-            let (r, g, b) = (p[0], p[1], p[2]);
-            *val = format!("\x1B[38;2;{};{};{}m{}", r, g, b, char::from(art[i][j]));
+pub fn blankTransparent(art: &mut AsciiArt, alpha: &GrayImage) {
+    // Transparent cells become uncolored spaces whatever intensity they were composited to
+    for (x, y, p) in alpha.enumerate_pixels() {
+        if p[0] < TRANSPARENT_BELOW {
+            if let Some(cell) = art.getMut(x as usize, y as usize) {
+                cell.clear();
+            }
         }
     }
-    coloredArt
 }
 
-pub fn to_string(art: &[Vec<u8>]) -> String {
-    String::from_utf8_lossy(&art.join(&10)).to_string()
+pub fn addAnsiTrueColor(art: &mut AsciiArt, img: &RgbImage, alpha: Option<&GrayImage>) {
+    for (x, y, p) in img.enumerate_pixels() {
+        let transparent = alpha.is_some_and(|a| a.get_pixel(x, y)[0] < TRANSPARENT_BELOW);
+        if let Some(cell) = art.getMut(x as usize, y as usize) {
+            // Transparent cells keep the terminal's own color
+            cell.fg = (!transparent).then_some(p.0);
+        }
+    }
 }
 
-pub fn display(art: &Vec<Vec<u8>>) {
-    for row in art {
-        let strRow = String::from_utf8_lossy(row);
-        println!("{strRow}");
-    }
+pub fn display(art: &AsciiArt) {
+    println!("{art}");
 }
 
-pub fn displayColored(art: &Vec<Vec<String>>) {
-    for row in art {
-        let strRow = row.join("");
-        println!("{strRow}");
-    }
+pub fn displayColored(art: &AsciiArt) {
+    println!("{}", art.toAnsi());
 }
 
-pub fn copyToClipboard(art: &[Vec<u8>]) {
-    let textToCopy = art
-        .iter()
-        .map(|row| String::from_utf8_lossy(row).to_string())
-        .collect::<Vec<String>>()
-        .join("\n");
+pub fn copyToClipboard(art: &AsciiArt) {
+    let textToCopy = art.to_string();
     let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
     ctx.set_contents(textToCopy.to_owned()).unwrap();
 }
@@ -244,6 +324,86 @@ mod tests {
         assert_eq!(weighted_ramp.len(), 256);
     }
 
+    #[test]
+    fn test_every_font_has_full_metrics() {
+        let ascGen = AsciiGenerator::new();
+        for (name, family) in ascGen.fontNames() {
+            assert!(!family.is_empty());
+            assert_eq!(ascGen.getChars(&name).len(), 95, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_validate_charset() {
+        let ascGen = AsciiGenerator::new();
+        assert_eq!(
+            ascGen.validateCharset("menlo", "@"),
+            Err(CharsetError::TooShort)
+        );
+        assert_eq!(
+            ascGen.validateCharset("menlo", "@█▓@"),
+            Err(CharsetError::Unsupported("█▓".to_string()))
+        );
+        assert_eq!(
+            ascGen.validateCharset("menlo", "@@"),
+            Err(CharsetError::TooFewLevels)
+        );
+        assert_eq!(ascGen.validateCharset("menlo", "@. "), Ok(()));
+    }
+
+    #[test]
+    fn test_every_preset_builds_a_ramp() {
+        let ascGen = AsciiGenerator::new();
+        for preset in ascGen.presets() {
+            let ramp = ascGen.ramp("menlo", &RampSource::Preset(preset.id.clone()));
+            assert!(ramp.stops().len() >= 2, "{}", preset.id);
+        }
+    }
+
+    #[test]
+    fn test_optimal_charset() {
+        let ascGen = AsciiGenerator::new();
+        let chars = ascGen.optimalCharset("menlo", 10);
+        assert_eq!(chars.chars().count(), 10);
+        assert!(chars.ends_with(' '));
+        assert_eq!(ascGen.validateCharset("menlo", &chars), Ok(()));
+        let ramp = ascGen.fontRamp("menlo", &chars);
+        assert_eq!(ramp.stops().len(), 10);
+        let widestGap = ramp
+            .stops()
+            .windows(2)
+            .map(|w| w[1].position - w[0].position)
+            .max()
+            .unwrap();
+        assert!(widestGap < 60, "{chars:?} leaves a gap of {widestGap}");
+
+        let all = ascGen.optimalCharset("menlo", 500);
+        assert_eq!(ascGen.fontRamp("menlo", &all).stops().len(), all.len());
+    }
+
+    #[test]
+    fn test_convert_flat_matches_rows() {
+        let ascGen = AsciiGenerator::new();
+        let ramp = ascGen.fontRamp("menlo", "@#. ");
+        let img = GrayImage::from_fn(9, 4, |x, y| Luma([(x * 28 + y) as u8]));
+        let art = ascGen.convert(&ramp, &img);
+        assert_eq!((art.width(), art.height()), (9, 4));
+        assert_eq!(
+            ascGen.convertFlat(&ramp, &img),
+            art.glyphs().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_transparent_cells_stay_blank() {
+        let mut art = AsciiArt::fromLines("@#");
+        let alpha = GrayImage::from_fn(2, 1, |x, _| Luma([x as u8 * 255]));
+        addAnsiTrueColor(&mut art, &RgbImage::new(2, 1), Some(&alpha));
+        blankTransparent(&mut art, &alpha);
+        assert_eq!(art.to_string(), " #");
+        assert_eq!(art.toAnsi(), " \x1B[0;38;2;0;0;0m#\x1B[0m");
+    }
+
     #[test]
     fn test_convert() {
         use std::time::Instant;
@@ -268,11 +428,12 @@ mod tests {
 
         let ascGen = AsciiGenerator::new();
 
-        let asciiArt = ascGen.convert(&font, &chars, &gray);
+        let ramp = ascGen.fontRamp(&font, &chars);
+        let asciiArt = ascGen.convert(&ramp, &gray);
 
         display(&asciiArt);
 
-        // let asciiArt = addAnsiTrueColor(&asciiArt, &imgRgb);
+        // addAnsiTrueColor(&mut asciiArt, &imgRgb, None);
 
         // displayColored(&asciiArt);
 
@@ -0,0 +1,112 @@
+#![allow(non_snake_case)]
+
+// Prints an image as art sized to the terminal, for MOTD and login banner scripts
+use std::{env, fs, process};
+
+use image::DynamicImage;
+use rusciii::{
+    asciiGenerator::AsciiGenerator,
+    imageHandler::{alphaMask, composite, loadImage, OutputSize, Tone},
+    ramp::Background,
+    settings::ConversionSettings,
+    worker::Stages,
+};
+use terminal_size::{terminal_size, Height, Width};
+
+const USAGE: &str =
+    "Usage: cli <image> [--cols N] [--rows N] [--font NAME] [--tone ID] [--background dark|light]";
+// Used when output isn't a terminal and nothing says otherwise
+const FALLBACK_SIZE: (u32, u32) = (80, 24);
+
+struct Args {
+    path: String,
+    columns: Option<u32>,
+    rows: Option<u32>,
+    font: Option<String>,
+    tone: Option<Tone>,
+    background: Background,
+}
+
+fn parseArgs(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        path: String::new(),
+        columns: None,
+        rows: None,
+        font: None,
+        tone: None,
+        background: Background::Dark,
+    };
+    let number = |flag: &str, value: Option<String>| -> Result<u32, String> {
+        value
+            .and_then(|v| v.parse().ok())
+            .filter(|&n| n > 0)
+            .ok_or_else(|| format!("{flag} needs a positive number"))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cols" => parsed.columns = Some(number("--cols", args.next())?),
+            "--rows" => parsed.rows = Some(number("--rows", args.next())?),
+            "--font" => parsed.font = Some(args.next().ok_or("--font needs a name")?),
+            "--tone" => {
+                let id = args.next().ok_or("--tone needs an id")?;
+                let tone = Tone::fromId(&id).ok_or_else(|| {
+                    let ids: Vec<_> = Tone::all().iter().map(|t| t.id()).collect();
+                    format!("Unknown tone {id}, expected one of {}", ids.join(", "))
+                })?;
+                parsed.tone = Some(tone);
+            }
+            "--background" => {
+                parsed.background = args
+                    .next()
+                    .ok_or("--background needs dark or light")?
+                    .parse()?
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}")),
+            _ if !parsed.path.is_empty() => return Err("Only one image can be given".to_string()),
+            _ => parsed.path = arg,
+        }
+    }
+    if parsed.path.is_empty() {
+        return Err("No image given".to_string());
+    }
+    Ok(parsed)
+}
+
+fn terminalSize() -> (u32, u32) {
+    // COLUMNS and LINES cover scripts whose output is piped rather than a tty
+    let fromEnv = |name: &str| env::var(name).ok().and_then(|v| v.parse().ok());
+    match terminal_size() {
+        Some((Width(w), Height(h))) => (w as u32, h as u32),
+        None => (
+            fromEnv("COLUMNS").unwrap_or(FALLBACK_SIZE.0),
+            fromEnv("LINES").unwrap_or(FALLBACK_SIZE.1),
+        ),
+    }
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
+
+fn main() {
+    let args = parseArgs(env::args().skip(1)).unwrap_or_else(|err| fail(format!("{err}\n{USAGE}")));
+    let ascGen = AsciiGenerator::new();
+    let defaults = ConversionSettings::default();
+    let job = ConversionSettings {
+        font: args.font.unwrap_or(defaults.font),
+        size: OutputSize::fitTerminal(terminalSize(), args.columns, args.rows),
+        tone: args.tone.unwrap_or(defaults.tone),
+        background: args.background,
+        ..defaults
+    };
+    let job = job.validate(&ascGen).unwrap_or_else(|err| fail(err));
+
+    let bytes =
+        fs::read(&args.path).unwrap_or_else(|err| fail(format!("Can't read {}: {err}", args.path)));
+    let img = loadImage(&bytes).unwrap_or_else(|err| fail(err));
+    let gray = DynamicImage::ImageRgb8(composite(&img, job.background.color())).into_luma8();
+    let mut stages = Stages::new(gray, alphaMask(&img));
+    let (art, _) = stages.render(&ascGen, &job);
+    println!("{art}");
+}
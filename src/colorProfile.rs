@@ -0,0 +1,345 @@
+use image::{DynamicImage, Rgb, Rgba};
+
+// XYZ (D50 white, as used by ICC profiles) to linear sRGB, Bradford adapted
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.6168667, -0.4906146],
+    [-0.9787684, 1.9161415, 0.033454],
+    [0.0719453, -0.2289914, 1.4052427],
+];
+
+// Columns are the D50 adapted sRGB primaries, as found in sRGB profiles
+const SRGB_TO_XYZ_D50: [[f32; 3]; 3] = [
+    [0.4360747, 0.3850649, 0.1430804],
+    [0.2225045, 0.7168786, 0.0606169],
+    [0.0139322, 0.0971045, 0.7141733],
+];
+
+/// Tone response curve of one channel, decoding stored values to linear light.
+#[derive(Clone, Debug, PartialEq)]
+enum Trc {
+    Gamma(f32),
+    Table(Vec<u16>),
+    /// ICC parametric curve, type 0-4 with up to 7 parameters
+    Parametric(u16, [f32; 7]),
+}
+
+impl Trc {
+    fn linear(&self, v: f32) -> f32 {
+        match self {
+            Trc::Gamma(g) => v.powf(*g),
+            Trc::Table(table) => {
+                let pos = v * (table.len() - 1) as f32;
+                let i = (pos.floor() as usize).min(table.len() - 2);
+                let f = pos - i as f32;
+                (table[i] as f32 * (1.0 - f) + table[i + 1] as f32 * f) / 65535.0
+            }
+            Trc::Parametric(kind, p) => {
+                let [g, a, b, c, d, e, f] = *p;
+                match kind {
+                    0 => v.powf(g),
+                    1 if v >= -b / a => (a * v + b).powf(g),
+                    1 => 0.0,
+                    2 if v >= -b / a => (a * v + b).powf(g) + c,
+                    2 => c,
+                    3 if v >= d => (a * v + b).powf(g),
+                    3 => c * v,
+                    4 if v >= d => (a * v + b).powf(g) + e,
+                    4 => c * v + f,
+                    _ => v,
+                }
+            }
+        }
+    }
+}
+
+/// Matrix/TRC RGB profile, the kind embedded by cameras, phones and editors
+/// (sRGB, Display P3, Adobe RGB, ProPhoto).
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbProfile {
+    toXyz: [[f32; 3]; 3],
+    trc: [Trc; 3],
+}
+
+impl RgbProfile {
+    pub fn parse(icc: &[u8]) -> Option<Self> {
+        if icc.get(16..20)? != b"RGB " || icc.get(36..40)? != b"acsp" {
+            return None;
+        }
+        let tagCount = be32(icc, 128)? as usize;
+        // Each entry takes 12 bytes, a bigger count than fits is a corrupt profile
+        if tagCount > icc.len().saturating_sub(132) / 12 {
+            return None;
+        }
+        let tag = |sig: &[u8; 4]| -> Option<&[u8]> {
+            (0..tagCount).find_map(|i| {
+                let entry = 132 + i * 12;
+                if icc.get(entry..entry + 4)? != sig {
+                    return None;
+                }
+                let offset = be32(icc, entry + 4)? as usize;
+                let size = be32(icc, entry + 8)? as usize;
+                // Both come from the file and can overflow a 32 bit usize
+                offset
+                    .checked_add(size)
+                    .and_then(|end| icc.get(offset..end))
+            })
+        };
+
+        let columns = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|sig| tag(sig).and_then(parseXyz));
+        let trc = [b"rTRC", b"gTRC", b"bTRC"].map(|sig| tag(sig).and_then(parseTrc));
+        let [Some(r), Some(g), Some(b)] = columns else {
+            return None;
+        };
+        let [Some(rt), Some(gt), Some(bt)] = trc else {
+            return None;
+        };
+        let toXyz = [0, 1, 2].map(|row| [r[row], g[row], b[row]]);
+        Some(Self {
+            toXyz,
+            trc: [rt, gt, bt],
+        })
+    }
+
+    pub fn isSrgb(&self) -> bool {
+        // sRGB primaries under another gamma still need converting, so the curves
+        // have to round trip every 8 bit value to within a level too
+        let primaries = self
+            .toXyz
+            .iter()
+            .flatten()
+            .zip(SRGB_TO_XYZ_D50.iter().flatten())
+            .all(|(a, b)| (a - b).abs() < 0.002);
+        let curves = self.trc.iter().all(|trc| {
+            (0..=255).all(|i| {
+                let encoded = srgbEncode(trc.linear(i as f32 / 255.0).clamp(0.0, 1.0)) * 255.0;
+                (encoded - i as f32).abs() <= 1.0
+            })
+        });
+        primaries && curves
+    }
+
+    pub fn toSrgb(&self, img: DynamicImage) -> DynamicImage {
+        let convert = self.converter();
+        if img.color().has_alpha() {
+            let mut rgba = img.into_rgba8();
+            rgba.pixels_mut().for_each(|p| {
+                let Rgb([r, g, b]) = convert([p[0], p[1], p[2]]);
+                *p = Rgba([r, g, b, p[3]]);
+            });
+            DynamicImage::ImageRgba8(rgba)
+        } else {
+            let mut rgb = img.into_rgb8();
+            rgb.pixels_mut()
+                .for_each(|p| *p = convert([p[0], p[1], p[2]]));
+            DynamicImage::ImageRgb8(rgb)
+        }
+    }
+
+    fn converter(&self) -> impl Fn([u8; 3]) -> Rgb<u8> {
+        // Linearize through per channel tables, then one matrix from profile RGB to linear sRGB
+        let linear: Vec<[f32; 256]> = self
+            .trc
+            .iter()
+            .map(|trc| {
+                let mut lut = [0_f32; 256];
+                lut.iter_mut()
+                    .enumerate()
+                    .for_each(|(i, slot)| *slot = trc.linear(i as f32 / 255.0));
+                lut
+            })
+            .collect();
+        let m = matMul(&XYZ_D50_TO_SRGB, &self.toXyz);
+        let mut encode = [0_u8; 4096];
+        encode.iter_mut().enumerate().for_each(|(i, slot)| {
+            *slot = (srgbEncode(i as f32 / 4095.0) * 255.0).round() as u8;
+        });
+        move |[r, g, b]| {
+            let lin = [
+                linear[0][r as usize],
+                linear[1][g as usize],
+                linear[2][b as usize],
+            ];
+            Rgb(m.map(|row| {
+                let v = row[0] * lin[0] + row[1] * lin[1] + row[2] * lin[2];
+                encode[(v.clamp(0.0, 1.0) * 4095.0).round() as usize]
+            }))
+        }
+    }
+}
+
+fn srgbEncode(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn matMul(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn s15Fixed16(data: &[u8], at: usize) -> Option<f32> {
+    Some(be32(data, at)? as i32 as f32 / 65536.0)
+}
+
+fn parseXyz(data: &[u8]) -> Option<[f32; 3]> {
+    if data.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        s15Fixed16(data, 8)?,
+        s15Fixed16(data, 12)?,
+        s15Fixed16(data, 16)?,
+    ])
+}
+
+fn parseTrc(data: &[u8]) -> Option<Trc> {
+    match data.get(0..4)? {
+        b"curv" => {
+            let count = be32(data, 8)? as usize;
+            let entry = |i: usize| -> Option<u16> {
+                Some(u16::from_be_bytes(
+                    data.get(12 + 2 * i..14 + 2 * i)?.try_into().ok()?,
+                ))
+            };
+            match count {
+                0 => Some(Trc::Gamma(1.0)),
+                // A single entry is a u8Fixed8 gamma
+                1 => Some(Trc::Gamma(entry(0)? as f32 / 256.0)),
+                _ => (0..count)
+                    .map(entry)
+                    .collect::<Option<Vec<u16>>>()
+                    .map(Trc::Table),
+            }
+        }
+        b"para" => {
+            let kind = u16::from_be_bytes(data.get(8..10)?.try_into().ok()?);
+            let paramCount = [1, 3, 4, 5, 7].get(kind as usize)?;
+            let mut params = [0_f32; 7];
+            for (i, p) in params.iter_mut().take(*paramCount).enumerate() {
+                *p = s15Fixed16(data, 12 + 4 * i)?;
+            }
+            Some(Trc::Parametric(kind, params))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn fixed(v: f32) -> [u8; 4] {
+        ((v * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    fn gammaCurve(gamma: f32) -> Vec<u8> {
+        let mut curv = b"curv\0\0\0\0".to_vec();
+        curv.extend(1_u32.to_be_bytes());
+        curv.extend(((gamma * 256.0).round() as u16).to_be_bytes());
+        curv.extend([0, 0]);
+        curv
+    }
+
+    // Minimal matrix/TRC profile with the given D50 primaries and a pure gamma curve
+    fn profile(primaries: [[f32; 3]; 3], gamma: f32) -> Vec<u8> {
+        profileWithCurve(primaries, gammaCurve(gamma))
+    }
+
+    fn profileWithCurve(primaries: [[f32; 3]; 3], curv: Vec<u8>) -> Vec<u8> {
+        let mut tags: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+        for (sig, xyz) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().zip(primaries) {
+            let mut data = b"XYZ \0\0\0\0".to_vec();
+            xyz.iter().for_each(|v| data.extend(fixed(*v)));
+            tags.push((sig, data));
+        }
+        for sig in [b"rTRC", b"gTRC", b"bTRC"] {
+            tags.push((sig, curv.clone()));
+        }
+
+        let mut icc = vec![0_u8; 128];
+        icc[16..20].copy_from_slice(b"RGB ");
+        icc[36..40].copy_from_slice(b"acsp");
+        icc.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        for (sig, data) in &tags {
+            icc.extend(*sig);
+            icc.extend((offset as u32).to_be_bytes());
+            icc.extend((data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        tags.iter().for_each(|(_, data)| icc.extend(data));
+        icc
+    }
+
+    fn convert(icc: &[u8], rgb: [u8; 3]) -> [u8; 3] {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(rgb)));
+        let out = RgbProfile::parse(icc).unwrap().toSrgb(img).into_rgb8();
+        out.get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn test_adobe_rgb_to_srgb() {
+        let adobe = profile(
+            [
+                [0.6097559, 0.3111242, 0.0194811],
+                [0.2052401, 0.625656, 0.0608902],
+                [0.149224, 0.0632197, 0.7448387],
+            ],
+            2.199219,
+        );
+        assert_eq!(convert(&adobe, [255, 255, 255]), [255, 255, 255]);
+        let gray = convert(&adobe, [128, 128, 128]);
+        assert!(gray.iter().all(|&v| (126..=130).contains(&v)), "{gray:?}");
+        // Adobe green lies outside sRGB and clips
+        let green = convert(&adobe, [0, 255, 0]);
+        assert_eq!((green[0], green[1]), (0, 255));
+    }
+
+    #[test]
+    fn test_display_p3_red_desaturates() {
+        let p3 = profile(
+            [
+                [0.515102, 0.2411995, -0.0010491],
+                [0.2919769, 0.692244, 0.0418873],
+                [0.1571537, 0.0665567, 0.7843242],
+            ],
+            2.2,
+        );
+        let red = convert(&p3, [200, 0, 0]);
+        assert!(red[0] > 200, "{red:?}");
+        assert_eq!(convert(&p3, [0, 0, 0]), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_detects_srgb() {
+        let primaries = [0, 1, 2].map(|c| SRGB_TO_XYZ_D50.map(|row| row[c]));
+        // The sRGB curve as a type 3 parametric curve
+        let mut para = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045]
+            .iter()
+            .for_each(|v| para.extend(fixed(*v)));
+        let srgb = profileWithCurve(primaries, para);
+        assert!(RgbProfile::parse(&srgb).unwrap().isSrgb());
+        let gamma18 = profile(primaries, 1.8);
+        assert!(!RgbProfile::parse(&gamma18).unwrap().isSrgb());
+    }
+
+    #[test]
+    fn test_rejects_non_rgb_profiles() {
+        let mut icc = profile([[0.0; 3]; 3], 1.0);
+        icc[16..20].copy_from_slice(b"GRAY");
+        assert_eq!(RgbProfile::parse(&icc), None);
+        assert_eq!(RgbProfile::parse(&[]), None);
+        // Claims billions of tags with none of them there
+        let mut truncated = profile([[0.0; 3]; 3], 1.0)[..140].to_vec();
+        truncated[128..132].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(RgbProfile::parse(&truncated), None);
+    }
+}
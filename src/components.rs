@@ -1,4 +1,5 @@
 pub mod select;
 pub mod slider;
+pub mod textfield;
 pub mod toggle;
 pub mod upload;
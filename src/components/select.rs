@@ -1,7 +1,29 @@
 use leptos::*;
+use web_sys::Event;
 
 #[component]
-pub fn Select(name: String, label: String) -> impl IntoView {
+pub fn Select<F>(
+    name: String,
+    label: String,
+    options: Vec<(String, String)>,
+    #[prop(into)] value: Signal<String>,
+    onChange: F,
+) -> impl IntoView
+where
+    F: Fn(Event) + 'static,
+{
+    let options = options
+        .into_iter()
+        .map(|(id, text)| {
+            let optionId = id.clone();
+            view! {
+                <option value=id selected=move || value() == optionId>
+                    {text}
+                </option>
+            }
+        })
+        .collect_view();
+
     view! {
         <fieldset>
             <label class="text-xl font-medium text-zinc-800" for=name.clone()>
@@ -11,9 +33,9 @@ pub fn Select(name: String, label: String) -> impl IntoView {
                 class="w-full p-2 rounded-sm bg-transparent border border-amber-500 text-sm"
                 name=name.clone()
                 id=name
+                on:change=onChange
             >
-                <option value="menlo">Menlo</option>
-                <option value="courierNew">Courier New</option>
+                {options}
             </select>
         </fieldset>
     }
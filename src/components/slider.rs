@@ -7,7 +7,7 @@ pub fn Slider<F>(
     label: String,
     min: i16,
     max: i16,
-    value: i16,
+    #[prop(into)] value: MaybeSignal<i16>,
     onInput: F,
 ) -> impl IntoView
 where
@@ -24,7 +24,7 @@ where
                 type="range"
                 min=min
                 max=max
-                value=value
+                prop:value=value
                 on:input=onInput
             />
         </fieldset>
@@ -0,0 +1,32 @@
+use leptos::*;
+use web_sys::Event;
+
+#[component]
+pub fn TextField<F>(
+    name: String,
+    label: String,
+    #[prop(into)] value: Signal<String>,
+    #[prop(into)] error: Signal<Option<String>>,
+    onInput: F,
+) -> impl IntoView
+where
+    F: Fn(Event) + 'static,
+{
+    view! {
+        <fieldset>
+            <label class="text-xl font-medium text-zinc-800" for=name.clone()>
+                {label}
+            </label>
+            <input
+                id=name.clone()
+                name=name
+                type="text"
+                class="w-full p-2 rounded-sm bg-transparent border border-amber-500 font-mono text-sm"
+                class:border-red-600=move || error().is_some()
+                prop:value=value
+                on:input=onInput
+            />
+            <p class="pt-1 text-sm text-red-600">{error}</p>
+        </fieldset>
+    }
+}
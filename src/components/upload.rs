@@ -1,6 +1,8 @@
 use leptos::*;
 use web_sys::{Event, MouseEvent};
 
+use crate::imageHandler::{ACCEPTED_TYPES, MAX_FILE_BYTES};
+
 #[component]
 pub fn Upload<F>(onUpload: F, inputRef: NodeRef<html::Input>) -> impl IntoView
 where
@@ -20,14 +22,17 @@ where
             <div class="absolute inset-0 p-8 border-b border-amber-500 flex flex-col justify-center">
                 <h4 class="text-xl font-medium text-center text-zinc-800">Upload image</h4>
                 <p class="text-sm font-normal text-center text-zinc-800">
-                    Supported image: png, jpg, jpeg, Max size: 5MB
+                    {format!(
+                        "Supported image: png, jpg, gif, webp, bmp, tiff, qoi, Max size: {}MB",
+                        MAX_FILE_BYTES / (1024 * 1024)
+                    )}
                 </p>
             </div>
             <input
                 type="file"
                 id="img"
                 name="img"
-                accept="image/png, image/jpg, image/jpeg"
+                accept=ACCEPTED_TYPES
                 class="hidden"
                 // Apart from stop propagation the call also needs to be undelegated to avoid error
                 on:click:undelegated=onUploadClick
@@ -0,0 +1,217 @@
+use crate::{asciiArt::AsciiArt, editor::Canvas};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayerKind {
+    Image,
+    Banner,
+    Drawing,
+}
+
+impl LayerKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LayerKind::Image => "Image",
+            LayerKind::Banner => "Banner",
+            LayerKind::Drawing => "Drawing",
+        }
+    }
+}
+
+/// One sheet of art placed on the composition. Live layers follow the converter
+/// or the banner text until they're kept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub id: u32,
+    pub name: String,
+    pub kind: LayerKind,
+    pub canvas: Canvas,
+    pub column: usize,
+    pub row: usize,
+    pub visible: bool,
+    // Blank cells show the layers below instead of covering them
+    pub spaceTransparent: bool,
+    pub live: bool,
+    // Art the layer was made from, what discarding its edits goes back to
+    source: AsciiArt,
+    stale: bool,
+}
+
+impl Layer {
+    fn new(id: u32, kind: LayerKind, art: AsciiArt) -> Self {
+        Self {
+            id,
+            name: format!("{} {id}", kind.label()),
+            kind,
+            canvas: Canvas::new(art.clone()),
+            column: 0,
+            row: 0,
+            visible: true,
+            spaceTransparent: kind != LayerKind::Image,
+            live: kind != LayerKind::Drawing,
+            source: art,
+            stale: false,
+        }
+    }
+
+    pub fn isStale(&self) -> bool {
+        // Its edits are holding back newer art from the converter or banner
+        self.stale
+    }
+
+    pub fn discardEdits(&mut self) {
+        self.canvas = Canvas::new(self.source.clone());
+        self.stale = false;
+    }
+
+    pub fn toLocal(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let local = (x.checked_sub(self.column)?, y.checked_sub(self.row)?);
+        let art = self.canvas.art();
+        (local.0 < art.width() && local.1 < art.height()).then_some(local)
+    }
+}
+
+/// Layers from bottom to top, flattened into the art that's shown and exported.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Composition {
+    layers: Vec<Layer>,
+    nextId: u32,
+}
+
+impl Composition {
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.id == id)
+    }
+
+    pub fn getMut(&mut self, id: u32) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.id == id)
+    }
+
+    pub fn live(&self, kind: LayerKind) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.live && l.kind == kind)
+    }
+
+    pub fn add(&mut self, kind: LayerKind, art: AsciiArt) -> u32 {
+        self.nextId += 1;
+        self.layers.push(Layer::new(self.nextId, kind, art));
+        self.nextId
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.layers.retain(|l| l.id != id);
+    }
+
+    pub fn moveLayer(&mut self, id: u32, up: bool) {
+        let Some(i) = self.layers.iter().position(|l| l.id == id) else {
+            return;
+        };
+        let j = if up { i + 1 } else { i.wrapping_sub(1) };
+        if j < self.layers.len() {
+            self.layers.swap(i, j);
+        }
+    }
+
+    pub fn updateLive(&mut self, kind: LayerKind, art: Option<AsciiArt>) {
+        // Edited layers hold on to their art and are flagged instead
+        let Some(i) = self.layers.iter().position(|l| l.live && l.kind == kind) else {
+            if let Some(art) = art.filter(|a| !a.isEmpty()) {
+                self.add(kind, art);
+            }
+            return;
+        };
+        let layer = &mut self.layers[i];
+        if layer.canvas.isEdited() {
+            layer.stale = art.as_ref() != Some(&layer.source);
+            if let Some(art) = art {
+                layer.source = art;
+            }
+            return;
+        }
+        match art {
+            Some(art) => {
+                layer.canvas = Canvas::new(art.clone());
+                layer.source = art;
+                layer.stale = false;
+            }
+            None => {
+                self.layers.remove(i);
+            }
+        }
+    }
+
+    pub fn keep(&mut self, id: u32) {
+        // Later art starts a new live layer rather than replacing this one
+        if let Some(layer) = self.getMut(id) {
+            layer.live = false;
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.layers
+            .iter()
+            .filter(|l| l.visible)
+            .fold((0, 0), |(w, h), l| {
+                let art = l.canvas.art();
+                (w.max(l.column + art.width()), h.max(l.row + art.height()))
+            })
+    }
+
+    pub fn flatten(&self) -> AsciiArt {
+        let (w, h) = self.size();
+        let mut flat = AsciiArt::new(w, h);
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            match layer.spaceTransparent {
+                true => flat.stamp(layer.canvas.art(), layer.column, layer.row),
+                false => flat.paste(layer.canvas.art(), layer.column, layer.row),
+            }
+        }
+        flat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_order_offsets_and_rules() {
+        let mut comp = Composition::default();
+        let image = comp.add(LayerKind::Image, AsciiArt::fromLines("####\n####"));
+        let banner = comp.add(LayerKind::Banner, AsciiArt::fromLines("a b"));
+        comp.getMut(banner).unwrap().column = 2;
+        assert_eq!(comp.flatten().to_string(), "##a#b\n#### ");
+
+        comp.getMut(banner).unwrap().spaceTransparent = false;
+        assert_eq!(comp.flatten().to_string(), "##a b\n#### ");
+
+        comp.moveLayer(banner, false);
+        assert_eq!(comp.flatten().to_string(), "####b\n#### ");
+        comp.getMut(image).unwrap().visible = false;
+        assert_eq!(comp.flatten().to_string(), "  a b");
+    }
+
+    #[test]
+    fn test_live_layers_keep_edits() {
+        let mut comp = Composition::default();
+        comp.updateLive(LayerKind::Image, Some(AsciiArt::fromLines("..")));
+        let id = comp.live(LayerKind::Image).unwrap().id;
+        comp.updateLive(LayerKind::Image, Some(AsciiArt::fromLines("::")));
+        assert_eq!(comp.flatten().to_string(), "::");
+
+        comp.getMut(id).unwrap().canvas.paint(0, 0, '#');
+        comp.updateLive(LayerKind::Image, Some(AsciiArt::fromLines("==")));
+        assert!(comp.get(id).unwrap().isStale());
+        comp.getMut(id).unwrap().discardEdits();
+        assert_eq!(comp.flatten().to_string(), "==");
+
+        comp.keep(id);
+        comp.updateLive(LayerKind::Image, Some(AsciiArt::fromLines("o")));
+        assert_eq!(comp.layers().len(), 2);
+        assert_eq!(comp.flatten().to_string(), "o=");
+        comp.updateLive(LayerKind::Image, None);
+        assert_eq!(comp.layers().len(), 1);
+    }
+}
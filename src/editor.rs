@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+
+use crate::asciiArt::{AsciiArt, Cell};
+
+// Oldest edits are dropped past this, each one only holds the cells it touched
+const MAX_HISTORY: usize = 200;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Tool {
+    #[default]
+    Paint,
+    Erase,
+    Fill,
+    Text,
+}
+
+impl Tool {
+    pub fn options() -> Vec<(String, String)> {
+        [Tool::Paint, Tool::Erase, Tool::Fill, Tool::Text]
+            .iter()
+            .map(|t| (t.id().to_string(), t.label().to_string()))
+            .collect()
+    }
+
+    pub fn fromId(id: &str) -> Option<Self> {
+        match id {
+            "paint" => Some(Tool::Paint),
+            "erase" => Some(Tool::Erase),
+            "fill" => Some(Tool::Fill),
+            "text" => Some(Tool::Text),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Tool::Paint => "paint",
+            Tool::Erase => "erase",
+            Tool::Fill => "fill",
+            Tool::Text => "text",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Paint => "Paint",
+            Tool::Erase => "Erase",
+            Tool::Fill => "Fill",
+            Tool::Text => "Text",
+        }
+    }
+}
+
+/// Cells one edit changed, with their contents before and after.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Edit {
+    changes: Vec<(usize, usize, Cell, Cell)>,
+}
+
+/// Converted art plus the edits made on top of it. Only glyphs are edited, so
+/// colors and attributes of a cell survive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Canvas {
+    art: AsciiArt,
+    history: VecDeque<Edit>,
+    undone: Vec<Edit>,
+    // Open edit a drag keeps adding to, so a whole stroke undoes at once
+    stroke: Option<Edit>,
+}
+
+impl Canvas {
+    pub fn new(art: AsciiArt) -> Self {
+        Self {
+            art,
+            ..Self::default()
+        }
+    }
+
+    pub fn art(&self) -> &AsciiArt {
+        &self.art
+    }
+
+    pub fn isEdited(&self) -> bool {
+        !self.history.is_empty() || self.stroke.as_ref().is_some_and(|e| !e.changes.is_empty())
+    }
+
+    pub fn canUndo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    pub fn canRedo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn beginStroke(&mut self) {
+        self.endStroke();
+        self.stroke = Some(Edit::default());
+    }
+
+    pub fn endStroke(&mut self) {
+        if let Some(edit) = self.stroke.take() {
+            self.record(edit);
+        }
+    }
+
+    pub fn paint(&mut self, x: usize, y: usize, glyph: char) {
+        // Outside a stroke every call is its own edit
+        match self.stroke.take() {
+            Some(mut edit) => {
+                self.setGlyph(&mut edit, x, y, glyph);
+                self.stroke = Some(edit);
+            }
+            None => {
+                let mut edit = Edit::default();
+                self.setGlyph(&mut edit, x, y, glyph);
+                self.record(edit);
+            }
+        }
+    }
+
+    pub fn erase(&mut self, x: usize, y: usize) {
+        self.paint(x, y, ' ');
+    }
+
+    pub fn fill(&mut self, x: usize, y: usize, glyph: char) {
+        // Replaces the 4-connected region of cells sharing the glyph at (x, y)
+        let Some(target) = self.art.get(x, y).map(|c| c.glyph) else {
+            return;
+        };
+        if target == glyph {
+            return;
+        }
+        let mut edit = Edit::default();
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if self.art.get(x, y).map(|c| c.glyph) != Some(target) {
+                continue;
+            }
+            self.setGlyph(&mut edit, x, y, glyph);
+            stack.push((x + 1, y));
+            stack.push((x, y + 1));
+            if x > 0 {
+                stack.push((x - 1, y));
+            }
+            if y > 0 {
+                stack.push((x, y - 1));
+            }
+        }
+        self.record(edit);
+    }
+
+    pub fn typeText(&mut self, x: usize, y: usize, text: &str) -> (usize, usize) {
+        // Lines start under x like a text box, returns the cell after the last char
+        let mut edit = Edit::default();
+        let (mut cx, mut cy) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                (cx, cy) = (x, cy + 1);
+                continue;
+            }
+            self.setGlyph(&mut edit, cx, cy, c);
+            cx += 1;
+        }
+        self.record(edit);
+        (cx, cy)
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.endStroke();
+        let Some(edit) = self.history.pop_back() else {
+            return false;
+        };
+        for &(x, y, before, _) in edit.changes.iter().rev() {
+            self.art[(x, y)] = before;
+        }
+        self.undone.push(edit);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.undone.pop() else {
+            return false;
+        };
+        for &(x, y, _, after) in &edit.changes {
+            self.art[(x, y)] = after;
+        }
+        self.history.push_back(edit);
+        true
+    }
+
+    fn setGlyph(&mut self, edit: &mut Edit, x: usize, y: usize, glyph: char) {
+        let Some(cell) = self.art.getMut(x, y) else {
+            return;
+        };
+        if cell.glyph == glyph {
+            return;
+        }
+        let before = *cell;
+        cell.glyph = glyph;
+        edit.changes.push((x, y, before, *cell));
+    }
+
+    fn record(&mut self, edit: Edit) {
+        if edit.changes.is_empty() {
+            return;
+        }
+        self.undone.clear();
+        self.history.push_back(edit);
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stroke_undoes_at_once() {
+        let mut canvas = Canvas::new(AsciiArt::fromLines("...\n..."));
+        canvas.beginStroke();
+        canvas.paint(0, 0, '#');
+        canvas.paint(1, 0, '#');
+        canvas.endStroke();
+        canvas.erase(2, 1);
+        canvas.paint(5, 5, '#');
+        assert_eq!(canvas.art().to_string(), "##.\n.. ");
+        assert!(canvas.undo());
+        assert_eq!(canvas.art().to_string(), "##.\n...");
+        assert!(canvas.undo());
+        assert!(!canvas.undo());
+        assert_eq!(canvas.art().to_string(), "...\n...");
+        assert!(canvas.redo());
+        assert_eq!(canvas.art().to_string(), "##.\n...");
+    }
+
+    #[test]
+    fn test_fill_keeps_colors() {
+        let mut art = AsciiArt::fromLines("..#\n.#.\n#..");
+        art[(0, 0)].fg = Some([255, 0, 0]);
+        let mut canvas = Canvas::new(art);
+        canvas.fill(0, 0, 'o');
+        assert_eq!(canvas.art().to_string(), "oo#\no#.\n#..");
+        assert_eq!(canvas.art()[(0, 0)].fg, Some([255, 0, 0]));
+        assert!(canvas.undo());
+        assert!(!canvas.isEdited());
+    }
+
+    #[test]
+    fn test_type_text_wraps_under_start() {
+        let mut canvas = Canvas::new(AsciiArt::new(4, 3));
+        assert_eq!(canvas.typeText(1, 0, "hi\nyo!!"), (5, 1));
+        assert_eq!(canvas.art().to_string(), " hi \n yo!\n    ");
+        canvas.paint(0, 2, '#');
+        assert!(canvas.undo());
+        canvas.paint(3, 2, '#');
+        assert!(!canvas.canRedo());
+    }
+}
@@ -0,0 +1,168 @@
+use image::DynamicImage;
+
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// Reads the EXIF orientation (1-8) from a JPEG, PNG or WebP file.
+pub fn orientation(bytes: &[u8]) -> Option<u8> {
+    let tiff = if bytes.starts_with(&[0xFF, 0xD8]) {
+        jpegExif(bytes)
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        pngExif(bytes)
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        webpExif(bytes)
+    } else {
+        None
+    }?;
+    tiffOrientation(tiff)
+}
+
+pub fn applyOrientation(img: DynamicImage, orientation: u8) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn jpegExif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 2;
+    while i + 4 <= bytes.len() && bytes[i] == 0xFF {
+        let marker = bytes[i + 1];
+        // Metadata segments all come before the start of scan
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let data = bytes.get(i + 4..i + 2 + len)?;
+        if marker == 0xE1 && data.starts_with(b"Exif\0\0") {
+            return Some(&data[6..]);
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+fn pngExif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 8;
+    while i + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[i..i + 4].try_into().ok()?) as usize;
+        let kind = &bytes[i + 4..i + 8];
+        // Lengths come from the file, so they can overflow a 32 bit usize
+        let end = (i + 8).checked_add(len)?;
+        let data = bytes.get(i + 8..end)?;
+        if kind == b"eXIf" {
+            return Some(data);
+        }
+        if kind == b"IDAT" || kind == b"IEND" {
+            return None;
+        }
+        i = end.checked_add(4)?;
+    }
+    None
+}
+
+fn webpExif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let kind = &bytes[i..i + 4];
+        let len = u32::from_le_bytes(bytes[i + 4..i + 8].try_into().ok()?) as usize;
+        let end = (i + 8).checked_add(len)?;
+        let data = bytes.get(i + 8..end)?;
+        if kind == b"EXIF" {
+            return Some(data.strip_prefix(b"Exif\0\0").unwrap_or(data));
+        }
+        // Chunks are padded to an even size
+        i = end.checked_add(len & 1)?;
+    }
+    None
+}
+
+fn tiffOrientation(tiff: &[u8]) -> Option<u8> {
+    let littleEndian = match tiff.get(0..4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let u16At = |at: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if littleEndian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32At = |at: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if littleEndian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+
+    let ifd = u32At(4)? as usize;
+    let entries = u16At(ifd)? as usize;
+    (0..entries)
+        .map(|e| ifd + 2 + e * 12)
+        .find(|&entry| u16At(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16At(entry + 8))
+        .and_then(|o| u8::try_from(o).ok())
+        .filter(|o| (1..=8).contains(o))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, GrayImage, Luma};
+
+    fn tiffWithOrientation(o: u16) -> Vec<u8> {
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend(8_u32.to_be_bytes());
+        tiff.extend(1_u16.to_be_bytes());
+        tiff.extend(ORIENTATION_TAG.to_be_bytes());
+        tiff.extend(3_u16.to_be_bytes());
+        tiff.extend(1_u32.to_be_bytes());
+        tiff.extend(o.to_be_bytes());
+        tiff.extend([0, 0]);
+        tiff
+    }
+
+    #[test]
+    fn test_jpeg_orientation() {
+        let payload = [b"Exif\0\0".to_vec(), tiffWithOrientation(6)].concat();
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend(((payload.len() + 2) as u16).to_be_bytes());
+        jpeg.extend(payload);
+        jpeg.extend([0xFF, 0xDA]);
+        assert_eq!(orientation(&jpeg), Some(6));
+        assert_eq!(orientation(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+    }
+
+    #[test]
+    fn test_png_orientation() {
+        let tiff = tiffWithOrientation(8);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend((tiff.len() as u32).to_be_bytes());
+        png.extend(b"eXIf");
+        png.extend(tiff);
+        png.extend([0, 0, 0, 0]);
+        assert_eq!(orientation(&png), Some(8));
+    }
+
+    #[test]
+    fn test_apply_orientation() {
+        // 2x1 image, dark pixel on the left
+        let img = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 1, |x, _| Luma([x as u8])));
+        let rotated = applyOrientation(img.clone(), 6);
+        assert_eq!(rotated.dimensions(), (1, 2));
+        assert_eq!(rotated.to_luma8().get_pixel(0, 0)[0], 0);
+        let transposed = applyOrientation(img, 5);
+        assert_eq!(transposed.dimensions(), (1, 2));
+        assert_eq!(transposed.to_luma8().get_pixel(0, 1)[0], 1);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use crate::asciiArt::AsciiArt;
+
+// Required after the printable ascii chars, though plenty of fonts stop before them
+const GERMAN_CHARS: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    FullWidth,
+    Kerning,
+    /// Controlled smushing rule bits, none of them set means universal smushing
+    Smushing(u32),
+}
+
+impl Layout {
+    fn fromHeader(oldLayout: i64, fullLayout: Option<i64>) -> Self {
+        // Full layout wins when present, old layout is kept for older fonts
+        match fullLayout {
+            Some(full) if full & 128 != 0 => Layout::Smushing((full & 63) as u32),
+            Some(full) if full & 64 != 0 => Layout::Kerning,
+            Some(_) => Layout::FullWidth,
+            None if oldLayout < 0 => Layout::FullWidth,
+            None if oldLayout == 0 => Layout::Kerning,
+            None => Layout::Smushing((oldLayout & 63) as u32),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FigletError {
+    NotFiglet,
+    BadHeader(String),
+    Truncated(u32),
+}
+
+impl std::fmt::Display for FigletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FigletError::NotFiglet => write!(f, "Not a FIGlet font, expected a flf2a header"),
+            FigletError::BadHeader(reason) => write!(f, "Invalid font header: {reason}"),
+            FigletError::Truncated(code) => write!(f, "Font ends before char {code}"),
+        }
+    }
+}
+
+/// Font in the FIGlet flf2 format, rendering text as rows of sub chars.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FigFont {
+    hardblank: char,
+    height: usize,
+    layout: Layout,
+    glyphs: HashMap<char, Vec<Vec<char>>>,
+}
+
+impl FigFont {
+    pub fn parse(flf: &str) -> Result<Self, FigletError> {
+        let mut lines = flf.lines();
+        let header = lines
+            .next()
+            .and_then(|h| h.strip_prefix("flf2a"))
+            .ok_or(FigletError::NotFiglet)?;
+        let mut headerChars = header.chars();
+        let hardblank = headerChars
+            .next()
+            .ok_or_else(|| FigletError::BadHeader("missing hardblank".to_string()))?;
+        let params = headerChars
+            .as_str()
+            .split_whitespace()
+            .map(|p| p.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| FigletError::BadHeader(e.to_string()))?;
+        let [height, _baseline, _maxLength, oldLayout, commentLines, ..] = params[..] else {
+            let reason = "expected at least 5 numbers".to_string();
+            return Err(FigletError::BadHeader(reason));
+        };
+        if height < 1 {
+            let reason = "height must be positive".to_string();
+            return Err(FigletError::BadHeader(reason));
+        }
+        let height = height as usize;
+        let layout = Layout::fromHeader(oldLayout, params.get(6).copied());
+        let mut lines = lines.skip(commentLines.max(0) as usize).peekable();
+
+        let mut glyphs = HashMap::new();
+        let mut readGlyph = |lines: &mut dyn Iterator<Item = &str>| {
+            let rows: Vec<Vec<char>> = lines.take(height).map(stripEndmarks).collect();
+            (rows.len() == height).then(|| padRows(rows))
+        };
+        for code in (32..=126).chain(GERMAN_CHARS) {
+            // Fonts that skip them go straight on to code tags or end
+            let tagged = |line: &&str| {
+                let code = line.split_whitespace().next().and_then(parseCode);
+                code.is_some()
+            };
+            if code > 126 && lines.peek().is_none_or(tagged) {
+                break;
+            }
+            let glyph = readGlyph(&mut lines).ok_or(FigletError::Truncated(code))?;
+            glyphs.insert(char::from_u32(code).expect("Code should be a char"), glyph);
+        }
+        // Code tagged chars follow, each after a line starting with its code
+        while let Some(tag) = lines.next() {
+            if tag.trim().is_empty() {
+                continue;
+            }
+            let code = tag.split_whitespace().next().and_then(parseCode);
+            let glyph =
+                readGlyph(&mut lines).ok_or_else(|| FigletError::Truncated(code.unwrap_or(0)))?;
+            if let Some(c) = code.and_then(char::from_u32) {
+                glyphs.insert(c, glyph);
+            }
+        }
+        Ok(Self {
+            hardblank,
+            height,
+            layout,
+            glyphs,
+        })
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn render(&self, text: &str) -> AsciiArt {
+        // Each line of text becomes a band of rows, chars the font lacks are skipped
+        let mut rows: Vec<String> = vec![];
+        for line in text.lines() {
+            let mut band = vec![vec![]; self.height];
+            for glyph in line.chars().filter_map(|c| self.glyphs.get(&c)) {
+                self.append(&mut band, glyph);
+            }
+            rows.extend(band.into_iter().map(|row| {
+                row.into_iter()
+                    .map(|c| if c == self.hardblank { ' ' } else { c })
+                    .collect::<String>()
+            }));
+        }
+        AsciiArt::fromLines(&rows.join("\n"))
+    }
+
+    fn append(&self, band: &mut [Vec<char>], glyph: &[Vec<char>]) {
+        let width = glyph.first().map_or(0, Vec::len);
+        let bandWidth = band.first().map_or(0, Vec::len);
+        let overlap = match self.layout {
+            Layout::FullWidth => 0,
+            Layout::Kerning | Layout::Smushing(_) => band
+                .iter()
+                .zip(glyph)
+                .map(|(row, g)| self.overlap(row, g))
+                .min()
+                .unwrap_or(0)
+                .min(bandWidth)
+                .min(width),
+        };
+        let start = bandWidth - overlap;
+        for (row, g) in band.iter_mut().zip(glyph) {
+            for (i, &b) in g.iter().enumerate() {
+                if i < overlap {
+                    let a = row[start + i];
+                    row[start + i] = match (a, b) {
+                        (' ', _) => b,
+                        (_, ' ') => a,
+                        _ => self.smush(a, b).unwrap_or(b),
+                    };
+                } else {
+                    row.push(b);
+                }
+            }
+        }
+    }
+
+    fn overlap(&self, row: &[char], glyph: &[char]) -> usize {
+        // How far the glyph can slide left before its chars hit the row, one
+        // further when the touching pair smushes into a single char
+        let trailing = row.iter().rev().take_while(|&&c| c == ' ').count();
+        let leading = glyph.iter().take_while(|&&c| c == ' ').count();
+        let touching = row
+            .len()
+            .checked_sub(trailing + 1)
+            .map(|i| row[i])
+            .zip(glyph.get(leading).copied());
+        match (self.layout, touching) {
+            (Layout::Smushing(_), Some((a, b))) if self.smush(a, b).is_some() => {
+                trailing + leading + 1
+            }
+            _ => trailing + leading,
+        }
+    }
+
+    fn smush(&self, a: char, b: char) -> Option<char> {
+        let Layout::Smushing(rules) = self.layout else {
+            return None;
+        };
+        let hb = self.hardblank;
+        if rules == 0 {
+            // Universal smushing, the later char wins but hardblanks stay apart
+            return (a != hb && b != hb).then_some(b);
+        }
+        if a == hb || b == hb {
+            return (rules & 32 != 0 && a == hb && b == hb).then_some(hb);
+        }
+        if rules & 1 != 0 && a == b {
+            return Some(a);
+        }
+        const BORDERS: &str = "|/\\[]{}()<>";
+        if rules & 2 != 0 {
+            if a == '_' && BORDERS.contains(b) {
+                return Some(b);
+            }
+            if b == '_' && BORDERS.contains(a) {
+                return Some(a);
+            }
+        }
+        if rules & 4 != 0 {
+            let class = |c: char| {
+                ["|", "/\\", "[]", "{}", "()", "<>"]
+                    .iter()
+                    .position(|class| class.contains(c))
+            };
+            if let (Some(ca), Some(cb)) = (class(a), class(b)) {
+                if ca != cb {
+                    return Some(if ca > cb { a } else { b });
+                }
+            }
+        }
+        if rules & 8 != 0 && ["[]", "][", "{}", "}{", "()", ")("].contains(&&*format!("{a}{b}")) {
+            return Some('|');
+        }
+        if rules & 16 != 0 {
+            match (a, b) {
+                ('/', '\\') => return Some('|'),
+                ('\\', '/') => return Some('Y'),
+                ('>', '<') => return Some('X'),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+fn stripEndmarks(line: &str) -> Vec<char> {
+    // The last char marks the end of a row, doubled on a glyph's last row
+    let line = line.trim_end();
+    match line.chars().last() {
+        Some(endmark) => line.trim_end_matches(endmark).chars().collect(),
+        None => vec![],
+    }
+}
+
+fn padRows(mut rows: Vec<Vec<char>>) -> Vec<Vec<char>> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    rows.iter_mut().for_each(|row| row.resize(width, ' '));
+    rows
+}
+
+fn parseCode(tag: &str) -> Option<u32> {
+    // Decimal, 0x hex or leading zero octal, negative codes are font specific
+    let code = if let Some(hex) = tag.strip_prefix("0x").or(tag.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if tag.len() > 1 && tag.starts_with('0') {
+        i64::from_str_radix(&tag[1..], 8)
+    } else {
+        tag.parse()
+    };
+    code.ok().and_then(|c| u32::try_from(c).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font(layout: &str, glyph: impl Fn(char) -> [&'static str; 2]) -> String {
+        let mut flf = format!("flf2a$ 2 2 4 {layout} 1\nTest font\n");
+        for c in (32..=126_u8).map(char::from) {
+            let [top, bottom] = match c {
+                'a'..='z' => glyph(c),
+                _ => ["$", "$"],
+            };
+            flf.push_str(&format!("{top}@\n{bottom}@@\n"));
+        }
+        flf
+    }
+
+    #[test]
+    fn test_layouts() {
+        let glyph = |c| match c {
+            'a' => ["a ", "a "],
+            'b' => ["|", "/"],
+            _ => ["/", "|"],
+        };
+        let full = FigFont::parse(&font("-1", glyph)).unwrap();
+        assert_eq!(full.render("a b").to_string(), "a  |\na  /");
+        let kerned = FigFont::parse(&font("0", glyph)).unwrap();
+        assert_eq!(kerned.render("ab").to_string(), "a|\na/");
+        // Equal chars smush by rule 1, / wins over | by the hierarchy of rule 4
+        let smushed = FigFont::parse(&font("5", glyph)).unwrap();
+        assert_eq!(smushed.render("bb").to_string(), "|\n/");
+        assert_eq!(smushed.render("bc").to_string(), "/\n/");
+        assert_eq!(smushed.render("a\nb").height(), 4);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(FigFont::parse("hello"), Err(FigletError::NotFiglet));
+        assert!(matches!(
+            FigFont::parse("flf2a$ 2 x"),
+            Err(FigletError::BadHeader(_))
+        ));
+        let truncated = font("0", |_| ["x", "x"])
+            .lines()
+            .take(40)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(matches!(
+            FigFont::parse(&truncated),
+            Err(FigletError::Truncated(_))
+        ));
+        let tagged = font("0", |_| ["x", "x"]) + "0x263A smiley\n:)@\n(:@@\n";
+        let font = FigFont::parse(&tagged).unwrap();
+        assert_eq!(font.render("☺").to_string(), ":)\n(:");
+    }
+}
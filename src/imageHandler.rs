@@ -1,18 +1,294 @@
 use image::{
-    imageops::dither, DynamicImage, GenericImage, GenericImageView, GrayImage, Luma, RgbImage,
+    imageops::{crop_imm, dither, resize, FilterType},
+    io::{Limits, Reader as ImageReader},
+    DynamicImage, GenericImage, GenericImageView, GrayImage, ImageDecoder, ImageError, ImageResult,
+    Luma, Rgb, RgbImage,
 };
 use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Cursor};
+
+use crate::{colorProfile::RgbProfile, exif, integralImage::cellAverages};
+
+/// Tone step run on the downsampled image to spread its intensities over the full range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Tone {
+    /// Maps the darkest and brightest pixel to 0 and 255
+    #[default]
+    Stretch,
+    /// Like stretch, but ignores the given percent of pixels at each end
+    AutoLevels(f32),
+    /// Flattens the histogram of the whole image
+    Equalize,
+    /// Equalizes tiles of the image separately, limiting how much each tile is boosted
+    Clahe { tiles: u32, clipLimit: f32 },
+}
+
+impl Tone {
+    pub fn all() -> [Tone; 4] {
+        [
+            Tone::Stretch,
+            Tone::AutoLevels(1.0),
+            Tone::Equalize,
+            Tone::Clahe {
+                tiles: 8,
+                clipLimit: 3.0,
+            },
+        ]
+    }
+
+    pub fn options() -> Vec<(String, String)> {
+        Tone::all()
+            .iter()
+            .map(|t| (t.id().to_string(), t.label().to_string()))
+            .collect()
+    }
+
+    pub fn fromId(id: &str) -> Option<Self> {
+        Tone::all().into_iter().find(|t| t.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Tone::Stretch => "stretch",
+            Tone::AutoLevels(_) => "autoLevels",
+            Tone::Equalize => "equalize",
+            Tone::Clahe { .. } => "clahe",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tone::Stretch => "Stretch contrast",
+            Tone::AutoLevels(_) => "Auto levels",
+            Tone::Equalize => "Equalize",
+            Tone::Clahe { .. } => "Adaptive equalize",
+        }
+    }
+}
+
+/// How transparent pixels are treated before the image is converted to gray.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Transparency {
+    /// Blends the image onto a solid color
+    Composite([u8; 3]),
+    /// Leaves fully transparent cells blank and uncolored
+    Blank,
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Transparency::Composite([255, 255, 255])
+    }
+}
+
+// Cells whose average alpha falls below this are treated as fully transparent
+pub const TRANSPARENT_BELOW: u8 = 8;
+
+pub fn parseHexColor(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn toHexColor([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub fn composite(img: &DynamicImage, background: [u8; 3]) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        let a = p[3] as u32;
+        Rgb([0, 1, 2]
+            .map(|c| ((p[c] as u32 * a + background[c] as u32 * (255 - a) + 127) / 255) as u8))
+    })
+}
+
+pub fn alphaMask(img: &DynamicImage) -> Option<GrayImage> {
+    // None for opaque images so the mask can be skipped entirely
+    if !img.color().has_alpha() {
+        return None;
+    }
+    let rgba = img.to_rgba8();
+    Some(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        Luma([rgba.get_pixel(x, y)[3]])
+    }))
+}
+
+// Pixels of the resampled image that are averaged into one output cell
+pub const CELL_WIDTH: u32 = 7;
+pub const CELL_HEIGHT: u32 = 14;
+// Largest art the size sliders go to, more only costs the worker memory
+pub const MAX_COLUMNS: u32 = 300;
+pub const MAX_ROWS: u32 = 150;
+
+/// How an image is placed in a box of columns and rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Fit {
+    /// Whole image inside the box, one side may come out shorter
+    #[default]
+    Contain,
+    /// Box fully covered, the overflowing side is cropped
+    Cover,
+    /// Exactly the box, ignoring the aspect ratio
+    Stretch,
+}
+
+impl Fit {
+    pub fn options() -> Vec<(String, String)> {
+        [Fit::Contain, Fit::Cover, Fit::Stretch]
+            .iter()
+            .map(|f| (f.id().to_string(), f.label().to_string()))
+            .collect()
+    }
+
+    pub fn fromId(id: &str) -> Option<Self> {
+        [Fit::Contain, Fit::Cover, Fit::Stretch]
+            .into_iter()
+            .find(|f| f.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Fit::Contain => "fit",
+            Fit::Cover => "fill",
+            Fit::Stretch => "stretch",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Fit::Contain => "Fit",
+            Fit::Cover => "Fill",
+            Fit::Stretch => "Stretch",
+        }
+    }
+}
+
+/// Size of the art in cells. Whichever side isn't given follows the image aspect.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputSize {
+    Columns(u32),
+    Rows(u32),
+    Box { columns: u32, rows: u32, fit: Fit },
+}
+
+impl Default for OutputSize {
+    fn default() -> Self {
+        OutputSize::Columns(100)
+    }
+}
+
+impl OutputSize {
+    pub fn fitTerminal(
+        (termColumns, termRows): (u32, u32),
+        columns: Option<u32>,
+        rows: Option<u32>,
+    ) -> Self {
+        // Overrides replace a side of the terminal, which otherwise keeps its last
+        // row free for the prompt printed after the art
+        OutputSize::Box {
+            columns: columns.unwrap_or(termColumns).max(1),
+            rows: rows.unwrap_or(termRows.saturating_sub(1)).max(1),
+            fit: Fit::Contain,
+        }
+    }
+
+    pub fn clamped(self) -> Self {
+        let (columns, rows) = (
+            |c: u32| c.clamp(1, MAX_COLUMNS),
+            |r: u32| r.clamp(1, MAX_ROWS),
+        );
+        match self {
+            OutputSize::Columns(c) => OutputSize::Columns(columns(c)),
+            OutputSize::Rows(r) => OutputSize::Rows(rows(r)),
+            OutputSize::Box {
+                columns: c,
+                rows: r,
+                fit,
+            } => OutputSize::Box {
+                columns: columns(c),
+                rows: rows(r),
+                fit,
+            },
+        }
+    }
+
+    pub fn grid(&self, (w, h): (u32, u32)) -> (u32, u32) {
+        // Columns and rows of the art for an image of the given pixel size
+        if w == 0 || h == 0 {
+            return (0, 0);
+        }
+        // Rows per column, measured in cells rather than pixels
+        let aspect = (h * CELL_WIDTH) as f32 / (w * CELL_HEIGHT) as f32;
+        let rowsFor = |c: u32| ((c as f32 * aspect).round() as u32).max(1);
+        let colsFor = |r: u32| ((r as f32 / aspect).round() as u32).max(1);
+        match *self {
+            OutputSize::Columns(c) => (c, rowsFor(c)),
+            OutputSize::Rows(r) => (colsFor(r), r),
+            OutputSize::Box { columns, rows, fit } => match fit {
+                Fit::Contain if rowsFor(columns) <= rows => (columns, rowsFor(columns)),
+                Fit::Contain => (colsFor(rows).min(columns), rows),
+                Fit::Cover | Fit::Stretch => (columns, rows),
+            },
+        }
+    }
+}
 
 pub trait ImageHandler {
+    fn resizeTo(&self, size: OutputSize) -> Self;
     fn downsample(self) -> Self;
+    fn downsampleNaive(self) -> Self;
     fn stretchContrast(self) -> Self;
+    fn autoLevels(self, clipPercent: f32) -> Self;
+    fn equalize(self) -> Self;
+    fn clahe(self, tiles: u32, clipLimit: f32) -> Self;
+    fn tone(self, tone: Tone) -> Self;
     fn dither(&mut self);
 }
 
 impl ImageHandler for GrayImage {
+    fn resizeTo(&self, size: OutputSize) -> Self {
+        // Resamples to exactly one cell worth of pixels per output char
+        let (w, h) = self.dimensions();
+        let (columns, rows) = size.grid((w, h));
+        let (nw, nh) = (columns * CELL_WIDTH, rows * CELL_HEIGHT);
+        let filter = if nw > w {
+            FilterType::Lanczos3
+        } else {
+            FilterType::Triangle
+        };
+        if let OutputSize::Box {
+            fit: Fit::Cover, ..
+        } = size
+        {
+            // Scale until the box is covered, then crop the overflow evenly from both ends
+            let scale = (nw as f32 / w as f32).max(nh as f32 / h as f32);
+            let sw = ((w as f32 * scale).round() as u32).max(nw);
+            let sh = ((h as f32 * scale).round() as u32).max(nh);
+            let scaled = resize(self, sw, sh, filter);
+            return crop_imm(&scaled, (sw - nw) / 2, (sh - nh) / 2, nw, nh).to_image();
+        }
+        resize(self, nw, nh, filter)
+    }
+
     fn downsample(self) -> Self {
+        cellAverages(&self, CELL_WIDTH, CELL_HEIGHT)
+    }
+
+    fn downsampleNaive(self) -> Self {
+        // Reference for the integral image path, kept for tests and benchmarks
         let (w, h) = self.dimensions();
-        let (fw, fh) = (7, 14);
+        let (fw, fh) = (CELL_WIDTH, CELL_HEIGHT);
         let (w, h) = (((w + fw - 1) / fw) as usize, ((h + fh - 1) / fh) as usize);
         let mut avgIntens = vec![vec![(0_u32, 0_u32); w]; h];
         for (x, y, p) in self.enumerate_pixels() {
@@ -48,12 +324,224 @@ impl ImageHandler for GrayImage {
         self
     }
 
+    fn autoLevels(mut self, clipPercent: f32) -> Self {
+        let hist = histogram(&self);
+        let clip = (self.len() as f32 * clipPercent / 100.0) as u32;
+        let mut count = 0;
+        let minP = (0..=255_u8)
+            .find(|&i| {
+                count += hist[i as usize];
+                count > clip
+            })
+            .unwrap_or(0);
+        count = 0;
+        let maxP = (0..=255_u8)
+            .rev()
+            .find(|&i| {
+                count += hist[i as usize];
+                count > clip
+            })
+            .unwrap_or(255);
+        if maxP <= minP {
+            return self;
+        }
+        let range = (maxP - minP) as f32;
+        self.iter_mut().for_each(|p| {
+            *p = (((*p).clamp(minP, maxP) - minP) as f32 * 255.0 / range).round() as u8
+        });
+        self
+    }
+
+    fn equalize(mut self) -> Self {
+        let lut = equalizeLut(&histogram(&self));
+        self.iter_mut().for_each(|p| *p = lut[*p as usize]);
+        self
+    }
+
+    fn clahe(mut self, tiles: u32, clipLimit: f32) -> Self {
+        let (w, h) = self.dimensions();
+        if w == 0 || h == 0 {
+            return self;
+        }
+        let (tx, ty) = (tiles.clamp(1, w), tiles.clamp(1, h));
+
+        // Clipped and redistributed histogram equalization per tile
+        let mut luts = vec![[0_u8; 256]; (tx * ty) as usize];
+        for j in 0..ty {
+            for i in 0..tx {
+                let (x0, x1) = (i * w / tx, (i + 1) * w / tx);
+                let (y0, y1) = (j * h / ty, (j + 1) * h / ty);
+                let mut hist = [0_u32; 256];
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        hist[self.get_pixel(x, y)[0] as usize] += 1;
+                    }
+                }
+                let n = (x1 - x0) * (y1 - y0);
+                let limit = ((clipLimit * n as f32 / 256.0) as u32).max(1);
+                let mut excess = 0;
+                hist.iter_mut().for_each(|c| {
+                    excess += c.saturating_sub(limit);
+                    *c = (*c).min(limit);
+                });
+                let (share, rest) = (excess / 256, excess % 256);
+                hist.iter_mut()
+                    .enumerate()
+                    .for_each(|(k, c)| *c += share + u32::from((k as u32) < rest));
+
+                let lut = &mut luts[(j * tx + i) as usize];
+                let mut cdf = 0;
+                for (k, slot) in lut.iter_mut().enumerate() {
+                    cdf += hist[k];
+                    *slot = (cdf as f32 * 255.0 / n as f32).round() as u8;
+                }
+            }
+        }
+
+        // Blend the four nearest tile mappings so tile borders don't show
+        let (tw, th) = (w as f32 / tx as f32, h as f32 / ty as f32);
+        let neighbours = |pos: f32, size: f32, count: u32| {
+            let g = (pos + 0.5) / size - 0.5;
+            let i0 = (g.floor().max(0.0) as u32).min(count - 1);
+            let i1 = (i0 + 1).min(count - 1);
+            (i0, i1, (g - i0 as f32).clamp(0.0, 1.0))
+        };
+        for (x, y, p) in self.enumerate_pixels_mut() {
+            let (i0, i1, fx) = neighbours(x as f32, tw, tx);
+            let (j0, j1, fy) = neighbours(y as f32, th, ty);
+            let v = p[0] as usize;
+            let at = |i: u32, j: u32| luts[(j * tx + i) as usize][v] as f32;
+            let top = at(i0, j0) * (1.0 - fx) + at(i1, j0) * fx;
+            let bottom = at(i0, j1) * (1.0 - fx) + at(i1, j1) * fx;
+            p[0] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        self
+    }
+
+    fn tone(self, tone: Tone) -> Self {
+        match tone {
+            Tone::Stretch => self.stretchContrast(),
+            Tone::AutoLevels(clip) => self.autoLevels(clip),
+            Tone::Equalize => self.equalize(),
+            Tone::Clahe { tiles, clipLimit } => self.clahe(tiles, clipLimit),
+        }
+    }
+
     fn dither(&mut self) {
         let colorMap: Vec<u8> = (0..=255).collect();
         // dither(self, &colorMap)
     }
 }
 
+pub const MAX_FILE_BYTES: usize = 5 * 1024 * 1024;
+// Refused before decoding, a small file can still claim a huge canvas
+pub const MAX_PIXELS: u64 = 50_000_000;
+// Longest side kept after decoding, far more than any art needs
+pub const MAX_SIDE: u32 = 4096;
+// Decoders the image crate is built with, AVIF would need the native dav1d decoder
+pub const ACCEPTED_TYPES: &str =
+    "image/png, image/jpeg, image/gif, image/webp, image/bmp, image/tiff, image/qoi, .qoi";
+
+#[derive(Debug)]
+pub enum LoadError {
+    TooLarge(usize),
+    TooManyPixels(u32, u32),
+    Decode(ImageError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::TooLarge(bytes) => write!(
+                f,
+                "Image is {:.1}MB, the limit is {}MB",
+                *bytes as f64 / (1024.0 * 1024.0),
+                MAX_FILE_BYTES / (1024 * 1024)
+            ),
+            LoadError::TooManyPixels(w, h) => write!(
+                f,
+                "Image is {w} × {h}, the limit is {} megapixels",
+                MAX_PIXELS / 1_000_000
+            ),
+            LoadError::Decode(err) => write!(f, "Unable to open image: {err}"),
+        }
+    }
+}
+
+impl From<ImageError> for LoadError {
+    fn from(err: ImageError) -> Self {
+        LoadError::Decode(err)
+    }
+}
+
+pub fn loadImage(bytes: &[u8]) -> Result<DynamicImage, LoadError> {
+    // Decodes the image upright and in sRGB, honoring its EXIF orientation and color profile
+    if bytes.len() > MAX_FILE_BYTES {
+        return Err(LoadError::TooLarge(bytes.len()));
+    }
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(ImageError::IoError)?;
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_PIXELS * 4);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let (w, h) = decoder.dimensions();
+    if w as u64 * h as u64 > MAX_PIXELS {
+        return Err(LoadError::TooManyPixels(w, h));
+    }
+    let profile = decoder
+        .icc_profile()
+        .ok()
+        .flatten()
+        .and_then(|icc: Vec<u8>| RgbProfile::parse(&icc))
+        .filter(|p| !p.isSrgb());
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    if w.max(h) > MAX_SIDE {
+        logProgress(&format!("Downscaling {w} × {h} image..."));
+        img = img.resize(MAX_SIDE, MAX_SIDE, FilterType::Triangle);
+    }
+    if let Some(profile) = profile {
+        logProgress("Converting embedded color profile to sRGB...");
+        img = profile.toSrgb(img);
+    }
+    Ok(match exif::orientation(bytes) {
+        Some(o) => exif::applyOrientation(img, o),
+        None => img,
+    })
+}
+
+fn logProgress(message: &str) {
+    // The cli prints its art on stdout, so off the browser notes go to stderr
+    #[cfg(target_arch = "wasm32")]
+    logging::log!("{message}");
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{message}");
+}
+
+pub fn histogram(img: &GrayImage) -> [u32; 256] {
+    let mut hist = [0_u32; 256];
+    img.iter().for_each(|&p| hist[p as usize] += 1);
+    hist
+}
+
+pub fn equalizeLut(hist: &[u32; 256]) -> [u8; 256] {
+    // Maps each intensity to its position in the cumulative histogram
+    let total: u32 = hist.iter().sum();
+    let cdfMin = hist.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let mut lut = [0_u8; 256];
+    let mut cdf = 0;
+    lut.iter_mut().enumerate().for_each(|(i, slot)| {
+        cdf += hist[i];
+        *slot = if total == cdfMin {
+            i as u8
+        } else {
+            (cdf.saturating_sub(cdfMin) as f32 * 255.0 / (total - cdfMin) as f32).round() as u8
+        }
+    });
+    lut
+}
+
 pub fn grayscale(img: &RgbImage) -> Vec<Vec<u8>> {
     let (w, h) = img.dimensions();
     let mut result = vec![vec![0; w as usize]; h as usize];
@@ -64,3 +552,116 @@ pub fn grayscale(img: &RgbImage) -> Vec<Vec<u8>> {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    #[test]
+    fn test_load_limits() {
+        let tooLarge = loadImage(&vec![0; MAX_FILE_BYTES + 1]);
+        assert!(matches!(tooLarge, Err(LoadError::TooLarge(_))));
+
+        let mut png = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(RgbImage::new(5000, 10))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let img = loadImage(png.get_ref()).unwrap();
+        assert_eq!(img.dimensions(), (MAX_SIDE, 8));
+    }
+
+    #[test]
+    fn test_output_size_grid() {
+        // 700x700 px is 100 columns by 50 rows of 7x14 cells
+        let square = (700, 700);
+        assert_eq!(OutputSize::Columns(100).grid(square), (100, 50));
+        assert_eq!(OutputSize::Rows(20).grid(square), (40, 20));
+        let boxed = |fit| OutputSize::Box {
+            columns: 80,
+            rows: 30,
+            fit,
+        };
+        assert_eq!(boxed(Fit::Contain).grid(square), (60, 30));
+        assert_eq!(boxed(Fit::Contain).grid((1400, 100)), (80, 3));
+        assert_eq!(boxed(Fit::Cover).grid(square), (80, 30));
+        assert_eq!(boxed(Fit::Stretch).grid((1, 1000)), (80, 30));
+        assert_eq!(
+            OutputSize::fitTerminal((80, 31), None, None),
+            boxed(Fit::Contain)
+        );
+        assert_eq!(
+            OutputSize::fitTerminal((200, 50), Some(80), Some(30)),
+            boxed(Fit::Contain)
+        );
+    }
+
+    #[test]
+    fn test_resize_to_whole_cells() {
+        let img = GrayImage::from_fn(300, 200, |x, _| Luma([x as u8]));
+        let cover = OutputSize::Box {
+            columns: 20,
+            rows: 20,
+            fit: Fit::Cover,
+        };
+        for size in [OutputSize::Columns(33), OutputSize::Rows(9), cover] {
+            let (columns, rows) = size.grid(img.dimensions());
+            let cells = img.resizeTo(size).downsample();
+            assert_eq!(cells.dimensions(), (columns, rows), "{size:?}");
+        }
+    }
+
+    #[test]
+    fn test_transparent_pixels() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(3, 1, |x, _| {
+            Rgba([0, 0, 0, [0, 128, 255][x as usize]])
+        }));
+        let flat = composite(&img, [255, 0, 0]);
+        assert_eq!(flat.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        assert_eq!(flat.get_pixel(1, 0), &Rgb([127, 0, 0]));
+        assert_eq!(flat.get_pixel(2, 0), &Rgb([0, 0, 0]));
+        let mask = alphaMask(&img).unwrap();
+        assert_eq!(mask.as_raw(), &vec![0, 128, 255]);
+        assert_eq!(alphaMask(&DynamicImage::new_rgb8(1, 1)), None);
+        assert_eq!(parseHexColor("#FF8000"), Some([255, 128, 0]));
+        assert_eq!(toHexColor([255, 128, 0]), "#ff8000");
+        assert_eq!(parseHexColor("fff"), None);
+    }
+
+    #[test]
+    fn test_auto_levels_ignores_outliers() {
+        let mut img = GrayImage::from_fn(10, 10, |x, _| Luma([100 + x as u8 * 5]));
+        img.put_pixel(0, 0, Luma([0]));
+        img.put_pixel(9, 9, Luma([255]));
+        let stretched = img.clone().stretchContrast();
+        let leveled = img.autoLevels(1.0);
+        assert_eq!(stretched.get_pixel(9, 1)[0], 145);
+        assert_eq!(leveled.get_pixel(0, 1)[0], 0);
+        assert_eq!(leveled.get_pixel(9, 1)[0], 255);
+    }
+
+    #[test]
+    fn test_equalize_uses_full_range() {
+        let img = GrayImage::from_fn(16, 16, |x, y| Luma([120 + ((x + y) % 8) as u8]));
+        let eq = img.equalize();
+        let (minP, maxP) = (eq.iter().min().unwrap(), eq.iter().max().unwrap());
+        assert_eq!((*minP, *maxP), (0, 255));
+    }
+
+    #[test]
+    fn test_clahe_lifts_dark_tile() {
+        // Left half in deep shadow, right half blown out, both with faint texture
+        let img = GrayImage::from_fn(32, 16, |x, y| {
+            let texture = ((x + y) % 4) as u8;
+            Luma([if x < 16 { 10 + texture } else { 240 + texture }])
+        });
+        let dims = img.dimensions();
+        let out = img.clahe(2, 4.0);
+        let spread = |x0: u32| {
+            let vals: Vec<u8> = (0..16).map(|y| out.get_pixel(x0, y)[0]).collect();
+            vals.iter().max().unwrap() - vals.iter().min().unwrap()
+        };
+        assert!(spread(2) > 9, "dark tile texture stays flat");
+        assert_eq!(dims, out.dimensions());
+    }
+}
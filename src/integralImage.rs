@@ -0,0 +1,135 @@
+use image::GrayImage;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Summed area table of a gray image. Any box sum is four lookups.
+pub struct IntegralImage {
+    width: usize,
+    // (width + 1) x (height + 1), with a zero first row and column
+    sums: Vec<u32>,
+}
+
+impl IntegralImage {
+    pub fn new(img: &GrayImage) -> Self {
+        // Sums wrap at 2^32, box sums stay exact as long as one box fits
+        let (w, h) = (img.width() as usize, img.height() as usize);
+        let stride = w + 1;
+        let mut sums = vec![0_u32; stride * (h + 1)];
+        for (y, row) in img.as_raw().chunks_exact(w.max(1)).take(h).enumerate() {
+            let (above, rest) = sums.split_at_mut((y + 1) * stride);
+            let prev = &above[y * stride..];
+            let current = &mut rest[..stride];
+            let mut running = 0_u32;
+            for (x, &p) in row.iter().enumerate() {
+                running = running.wrapping_add(p as u32);
+                current[x + 1] = running;
+            }
+            addRow(current, prev);
+        }
+        Self { width: w, sums }
+    }
+
+    pub fn boxSum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> u32 {
+        // Sum of pixels in [x0, x1) x [y0, y1)
+        let stride = self.width + 1;
+        let at = |x: usize, y: usize| self.sums[y * stride + x];
+        at(x1, y1)
+            .wrapping_sub(at(x0, y1))
+            .wrapping_sub(at(x1, y0))
+            .wrapping_add(at(x0, y0))
+    }
+}
+
+// Four sums per add on wasm builds with simd128, see .cargo/config.toml
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+fn addRow(current: &mut [u32], prev: &[u32]) {
+    use core::arch::wasm32::*;
+    assert_eq!(current.len(), prev.len());
+    let lanes = current.len() / 4 * 4;
+    for i in (0..lanes).step_by(4) {
+        // SAFETY: i + 4 <= lanes <= len of both slices, and v128 loads need no alignment
+        unsafe {
+            let a = v128_load(current.as_ptr().add(i) as *const v128);
+            let b = v128_load(prev.as_ptr().add(i) as *const v128);
+            v128_store(current.as_mut_ptr().add(i) as *mut v128, u32x4_add(a, b));
+        }
+    }
+    addRowScalar(&mut current[lanes..], &prev[lanes..]);
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+fn addRow(current: &mut [u32], prev: &[u32]) {
+    addRowScalar(current, prev);
+}
+
+fn addRowScalar(current: &mut [u32], prev: &[u32]) {
+    current
+        .iter_mut()
+        .zip(prev)
+        .for_each(|(c, p)| *c = c.wrapping_add(*p));
+}
+
+pub fn cellAverages(img: &GrayImage, cellWidth: u32, cellHeight: u32) -> GrayImage {
+    // Average of every cell, edge cells only counting the pixels they cover
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let (fw, fh) = (cellWidth as usize, cellHeight as usize);
+    let (cols, rows) = (w.div_ceil(fw), h.div_ceil(fh));
+    let integral = IntegralImage::new(img);
+    let mut out = vec![0_u8; cols * rows];
+    let fillRow = |(cy, row): (usize, &mut [u8])| {
+        let (y0, y1) = (cy * fh, ((cy + 1) * fh).min(h));
+        for (cx, cell) in row.iter_mut().enumerate() {
+            let (x0, x1) = (cx * fw, ((cx + 1) * fw).min(w));
+            let count = ((x1 - x0) * (y1 - y0)) as u32;
+            let sum = integral.boxSum(x0, y0, x1, y1);
+            // Rounds half up, like the f64 average it replaces
+            *cell = ((2 * sum as u64 + count as u64) / (2 * count as u64)) as u8;
+        }
+    };
+    #[cfg(feature = "parallel")]
+    out.par_chunks_mut(cols.max(1))
+        .enumerate()
+        .for_each(fillRow);
+    #[cfg(not(feature = "parallel"))]
+    out.chunks_mut(cols.max(1)).enumerate().for_each(fillRow);
+    GrayImage::from_raw(cols as u32, rows as u32, out).expect("Cells should fill the image")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imageHandler::ImageHandler;
+    use image::Luma;
+
+    #[test]
+    fn test_box_sum() {
+        let img = GrayImage::from_fn(5, 4, |x, y| Luma([(x + 10 * y) as u8]));
+        let integral = IntegralImage::new(&img);
+        assert_eq!(
+            integral.boxSum(0, 0, 5, 4),
+            img.iter().map(|&p| p as u32).sum::<u32>()
+        );
+        assert_eq!(integral.boxSum(1, 2, 3, 3), 21 + 22);
+        assert_eq!(integral.boxSum(2, 2, 2, 4), 0);
+    }
+
+    #[test]
+    fn test_matches_naive_downsample() {
+        let img = GrayImage::from_fn(103, 61, |x, y| Luma([((x * 7 + y * 13) % 256) as u8]));
+        let fast = cellAverages(&img, 7, 14);
+        assert_eq!(fast, img.downsampleNaive());
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    fn test_simd_add_row_matches_scalar() {
+        // Odd length leaves a tail for the scalar loop, big values check wrapping
+        let prev: Vec<u32> = (0..23).map(|i| u32::MAX - i * 1000).collect();
+        let row: Vec<u32> = (0..23).map(|i| i * 7919).collect();
+        let (mut simd, mut scalar) = (row.clone(), row);
+        addRow(&mut simd, &prev);
+        addRowScalar(&mut scalar, &prev);
+        assert_eq!(simd, scalar);
+    }
+}
@@ -0,0 +1,20 @@
+#![allow(dead_code, unused, non_snake_case, clippy::new_without_default)]
+
+pub mod adjustments;
+pub mod app;
+pub mod asciiArt;
+pub mod asciiGenerator;
+pub mod colorProfile;
+pub mod components;
+pub mod composition;
+pub mod editor;
+pub mod exif;
+pub mod figlet;
+pub mod imageHandler;
+pub mod integralImage;
+pub mod ramp;
+pub mod sections;
+pub mod session;
+pub mod settings;
+pub mod utils;
+pub mod worker;
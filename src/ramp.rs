@@ -0,0 +1,318 @@
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+
+use crate::imageHandler::{equalizeLut, histogram};
+
+/// A glyph placed at a gray level on the ramp, 0 being the darkest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RampStop {
+    pub glyph: char,
+    pub position: u8,
+}
+
+/// Remaps pixel intensities before they are looked up on the ramp.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RampCurve {
+    Linear,
+    /// Power curve, values above 1 darken the midtones
+    Gamma(f32),
+    /// Logistic curve, higher strength pushes midtones towards the ends
+    SCurve(f32),
+    /// Spreads the image histogram evenly across the ramp
+    Equalized,
+}
+
+/// How the chars of a preset are placed on the ramp.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Spacing {
+    /// Evenly in the listed order, dark to light
+    Even,
+    /// By their measured intensity in the selected font
+    Font,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RampPreset {
+    pub id: String,
+    pub name: String,
+    pub chars: String,
+    pub spacing: Spacing,
+}
+
+pub fn loadPresets() -> Vec<RampPreset> {
+    let jsonData = include_str!("../data/defaultRamp.json");
+    serde_json::from_str(jsonData).expect("Failed to parse default ramp json")
+}
+
+/// Where the chars of the ramp come from, either a preset id or a user typed charset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RampSource {
+    Preset(String),
+    Custom(String),
+}
+
+impl Default for RampSource {
+    fn default() -> Self {
+        RampSource::Preset("detailed".to_string())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CharsetError {
+    TooShort,
+    Unsupported(String),
+    TooFewLevels,
+}
+
+impl std::fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CharsetError::TooShort => write!(f, "Enter at least two chars"),
+            CharsetError::Unsupported(chars) => write!(f, "Not measured for this font: {chars}"),
+            CharsetError::TooFewLevels => write!(f, "Chars need at least two distinct intensities"),
+        }
+    }
+}
+
+/// Color the art is shown on. Light glyphs on a dark background need the ramp inverted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Background {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Background {
+    pub fn color(&self) -> [u8; 3] {
+        match self {
+            Background::Light => [255, 255, 255],
+            Background::Dark => [0, 0, 0],
+        }
+    }
+}
+
+impl std::str::FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "light" => Ok(Background::Light),
+            "dark" => Ok(Background::Dark),
+            _ => Err(format!("Unknown background {s}, expected dark or light")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    stops: Vec<RampStop>,
+    curve: RampCurve,
+    inverted: bool,
+}
+
+impl Ramp {
+    pub fn fromStops(stops: impl IntoIterator<Item = (char, u8)>) -> Self {
+        let mut stops: Vec<RampStop> = stops
+            .into_iter()
+            .map(|(glyph, position)| RampStop { glyph, position })
+            .collect();
+        assert!(!stops.is_empty(), "Ramp needs at least one char");
+        // Stable sort so the first char given for a position wins
+        stops.sort_by_key(|s| s.position);
+        stops.dedup_by_key(|s| s.position);
+        Self {
+            stops,
+            curve: RampCurve::Linear,
+            inverted: false,
+        }
+    }
+
+    pub fn fromCharset(chars: &str) -> Self {
+        // Spreads the chars evenly, dark to light. Repeating a char widens its band.
+        let n = chars.chars().count();
+        let last = n.saturating_sub(1).max(1) as f32;
+        Self::fromStops(
+            chars
+                .chars()
+                .enumerate()
+                .map(|(i, c)| (c, (i as f32 * 255.0 / last).round() as u8)),
+        )
+    }
+
+    pub fn withCurve(mut self, curve: RampCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn withInvert(mut self, inverted: bool) -> Self {
+        // Bright pixels map to dense glyphs, for light text on a dark background
+        self.inverted = inverted;
+        self
+    }
+
+    pub fn forBackground(self, background: Background) -> Self {
+        self.withInvert(background == Background::Dark)
+    }
+
+    pub fn isInverted(&self) -> bool {
+        self.inverted
+    }
+
+    pub fn curve(&self) -> RampCurve {
+        self.curve
+    }
+
+    pub fn stops(&self) -> &[RampStop] {
+        &self.stops
+    }
+
+    pub fn glyphs(&self) -> String {
+        self.stops.iter().map(|s| s.glyph).collect()
+    }
+
+    pub fn nearest(&self, value: u8) -> &RampStop {
+        let i = self.stops.partition_point(|s| s.position < value);
+        if i == 0 {
+            return &self.stops[0];
+        }
+        if i == self.stops.len() {
+            return &self.stops[i - 1];
+        }
+        let (lo, hi) = (&self.stops[i - 1], &self.stops[i]);
+        if value - lo.position < hi.position - value {
+            lo
+        } else {
+            hi
+        }
+    }
+
+    pub fn glyphAt(&self, position: u8) -> Option<char> {
+        self.stops
+            .binary_search_by_key(&position, |s| s.position)
+            .ok()
+            .map(|i| self.stops[i].glyph)
+    }
+
+    pub fn toneCurve(&self, img: &GrayImage) -> [u8; 256] {
+        let mut curve: [u8; 256] = [0; 256];
+        match self.curve {
+            RampCurve::Linear => curve
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, slot)| *slot = i as u8),
+            RampCurve::Gamma(gamma) => curve.iter_mut().enumerate().for_each(|(i, slot)| {
+                *slot = ((i as f32 / 255.0).powf(gamma) * 255.0).round() as u8
+            }),
+            RampCurve::SCurve(strength) => {
+                let k = strength.max(f32::EPSILON);
+                let sigmoid = |x: f32| 1.0 / (1.0 + (-k * (x - 0.5)).exp());
+                let (lo, hi) = (sigmoid(0.0), sigmoid(1.0));
+                curve.iter_mut().enumerate().for_each(|(i, slot)| {
+                    let y = (sigmoid(i as f32 / 255.0) - lo) / (hi - lo);
+                    *slot = (y * 255.0).round() as u8
+                })
+            }
+            RampCurve::Equalized => curve = equalizeLut(&histogram(img)),
+        }
+        if self.inverted {
+            curve.iter_mut().for_each(|v| *v = 255 - *v);
+        }
+        curve
+    }
+
+    pub fn levels(&self) -> [u8; 256] {
+        // Nearest stop position for every gray level, used as the palette when dithering
+        let mut levels: [u8; 256] = [0; 256];
+        levels
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, slot)| *slot = self.nearest(i as u8).position);
+        levels
+    }
+
+    pub fn lookupTable(&self, img: &GrayImage) -> [char; 256] {
+        let curve = self.toneCurve(img);
+        let mut table: [char; 256] = [' '; 256];
+        table
+            .iter_mut()
+            .zip(curve)
+            .for_each(|(slot, v)| *slot = self.nearest(v).glyph);
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_from_charset_spreads_evenly() {
+        let ramp = Ramp::fromCharset("@#. ");
+        let positions: Vec<u8> = ramp.stops().iter().map(|s| s.position).collect();
+        assert_eq!(positions, vec![0, 85, 170, 255]);
+        assert_eq!(ramp.glyphs(), "@#. ");
+    }
+
+    #[test]
+    fn test_presets_have_unique_ids() {
+        let presets = loadPresets();
+        let mut ids: Vec<&str> = presets.iter().map(|p| p.id.as_str()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), presets.len());
+        assert!(presets
+            .iter()
+            .any(|p| RampSource::default() == RampSource::Preset(p.id.clone())));
+    }
+
+    #[test]
+    fn test_from_stops_keeps_first_duplicate() {
+        let ramp = Ramp::fromStops([(' ', 255), ('@', 0), ('#', 0)]);
+        assert_eq!(ramp.glyphs(), "@ ");
+    }
+
+    #[test]
+    fn test_nearest_breaks_ties_upwards() {
+        let ramp = Ramp::fromStops([('@', 0), (' ', 10)]);
+        assert_eq!(ramp.nearest(4).glyph, '@');
+        assert_eq!(ramp.nearest(5).glyph, ' ');
+        assert_eq!(ramp.nearest(255).glyph, ' ');
+    }
+
+    #[test]
+    fn test_curves_keep_end_points() {
+        let img = GrayImage::from_fn(4, 4, |x, y| Luma([(x * 16 + y * 4) as u8]));
+        for curve in [
+            RampCurve::Linear,
+            RampCurve::Gamma(2.2),
+            RampCurve::SCurve(8.0),
+        ] {
+            let tone = Ramp::fromCharset("@ ").withCurve(curve).toneCurve(&img);
+            assert_eq!((tone[0], tone[255]), (0, 255), "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn test_inverted_reverses_lookup() {
+        let img = GrayImage::new(0, 0);
+        let ramp = Ramp::fromCharset("@#. ");
+        let table = ramp.lookupTable(&img);
+        let inverted = ramp.forBackground(Background::Dark).lookupTable(&img);
+        assert_eq!((table[0], table[255]), ('@', ' '));
+        assert_eq!((inverted[0], inverted[255]), (' ', '@'));
+        assert_eq!("Dark".parse(), Ok(Background::Dark));
+        assert!("grey".parse::<Background>().is_err());
+    }
+
+    #[test]
+    fn test_equalized_spreads_histogram() {
+        let img = GrayImage::from_fn(2, 2, |x, y| Luma([100 + (x + 2 * y) as u8]));
+        let tone = Ramp::fromCharset("@ ")
+            .withCurve(RampCurve::Equalized)
+            .toneCurve(&img);
+        assert_eq!(&tone[100..104], &[0, 85, 170, 255]);
+    }
+}
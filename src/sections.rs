@@ -1,2 +1,6 @@
+pub mod adjustments;
 pub mod artpanel;
+pub mod banner;
+pub mod layers;
+pub mod presets;
 pub mod sidebar;
@@ -0,0 +1,109 @@
+use leptos::*;
+use web_sys::{Event, MouseEvent};
+
+use crate::{
+    adjustments::{Adjustment, Pipeline},
+    components::{select::*, slider::*},
+};
+
+#[component]
+pub fn Adjustments(
+    pipeline: ReadSignal<Pipeline>,
+    setPipeline: WriteSignal<Pipeline>,
+) -> impl IntoView {
+    let (newStep, setNewStep) = create_signal(Adjustment::options()[0].0.clone());
+
+    // Rows are keyed by position and kind so dragging a slider doesn't re-render its row
+    let rows = move || {
+        pipeline.with(|p| {
+            p.steps
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (i, s.id()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let stepRow = move |(i, _): (usize, &'static str)| {
+        let step = pipeline.with_untracked(|p| p.steps[i]);
+        let (min, max) = step.sliderRange();
+
+        let onStepInput = move |e: Event| {
+            let v: i16 = event_target_value(&e)
+                .parse()
+                .expect("Adjustment should be a number");
+            setPipeline.update(|p| p.steps[i] = p.steps[i].withSliderValue(v));
+        };
+        let onMoveUp = move |_: MouseEvent| setPipeline.update(|p| p.moveStep(i, true));
+        let onMoveDown = move |_: MouseEvent| setPipeline.update(|p| p.moveStep(i, false));
+        let onRemove = move |_: MouseEvent| {
+            logging::log!("Removing {} adjustment...", step.label());
+            setPipeline.update(|p| {
+                p.steps.remove(i);
+            })
+        };
+
+        view! {
+            <div class="relative">
+                {if step.hasSlider() {
+                    view! {
+                        <Slider
+                            name=format!("{}{}", step.id(), i)
+                            label=step.label().to_string()
+                            min=min
+                            max=max
+                            value=Signal::derive(move || {
+                                pipeline.with(|p| p.steps.get(i).map_or(0, |s| s.sliderValue()))
+                            })
+                            onInput=onStepInput
+                        />
+                    }
+                        .into_view()
+                } else {
+                    view! {
+                        <fieldset>
+                            <span class="text-xl font-medium text-zinc-800">{step.label()}</span>
+                        </fieldset>
+                    }
+                        .into_view()
+                }}
+                <div class="absolute top-0 right-0 flex space-x-3 text-sm text-zinc-800">
+                    <button title="Move up" on:click=onMoveUp>"↑"</button>
+                    <button title="Move down" on:click=onMoveDown>"↓"</button>
+                    <button title="Remove" on:click=onRemove>"✕"</button>
+                </div>
+            </div>
+        }
+    };
+
+    let onNewStepChange = move |e: Event| setNewStep(event_target_value(&e));
+
+    let onAddStep = move |_: MouseEvent| {
+        let step = Adjustment::fromId(&newStep()).expect("Adjustment option should be known");
+        logging::log!("Adding {} adjustment...", step.label());
+        setPipeline.update(|p| p.steps.push(step));
+    };
+
+    view! {
+        <div class="flex flex-col space-y-8">
+            <For each=rows key=|row| *row children=stepRow/>
+            <div class="flex flex-row items-end space-x-2">
+                <div class="flex-1">
+                    <Select
+                        name="newAdjustment".to_string()
+                        label="Add adjustment".to_string()
+                        options=Adjustment::options()
+                        value=newStep
+                        onChange=onNewStepChange
+                    />
+                </div>
+                <button
+                    class="p-2 rounded-sm border border-amber-500 text-sm font-medium text-zinc-800 hover:bg-amber-100"
+                    on:click=onAddStep
+                >
+                    Add
+                </button>
+            </div>
+        </div>
+    }
+}
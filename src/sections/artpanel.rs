@@ -6,41 +6,377 @@ use image::{
     DynamicImage, GenericImageView, GrayImage,
 };
 use leptos::*;
+use web_sys::{Event, KeyboardEvent, MouseEvent};
 
 use crate::{
     app::Test,
-    asciiGenerator::{self, to_string, AsciiGenerator},
-    components::{select::*, slider::*, upload::*},
-    utils::AsciiColorMap,
+    asciiArt::AsciiArt,
+    asciiGenerator::{self, AsciiGenerator},
+    components::{select::*, slider::*, toggle::*, upload::*},
+    composition::{Composition, Layer, LayerKind},
+    editor::{Canvas, Tool},
+    ramp::{Background, Ramp},
+    worker::StageTiming,
 };
 
 #[component]
-pub fn ArtPanel<F>(image: F, dither: ReadSignal<bool>) -> impl IntoView
-where
-    F: Fn() -> Option<GrayImage> + 'static,
-{
-    let font = "courierPrime".to_string();
-    let chars = "@#MBHA&Gh93X25Sisr;:,. ".to_string();
-    let ascGen = AsciiGenerator::new();
-    let (intensityMap, charMap) = ascGen.getIntensityDistAndCharMap(&font, &chars);
-    let ascColorMap = AsciiColorMap::new(intensityMap);
-    let asciiArt = move || {
-        let art: Vec<Vec<u8>> = match image().as_ref() {
-            Some(img) => match dither() {
-                true => ascGen.convertWithDither(&font, &chars, img),
-                false => ascGen.convert(&font, &chars, img),
-            },
-            None => {
-                vec![0_u8; 0];
-                vec![] as Vec<Vec<u8>>
+pub fn ArtPanel(
+    composition: ReadSignal<Composition>,
+    setComposition: WriteSignal<Composition>,
+    selected: ReadSignal<Option<u32>>,
+    timings: Signal<Vec<StageTiming>>,
+    font: ReadSignal<String>,
+    background: ReadSignal<Background>,
+    setBackground: WriteSignal<Background>,
+) -> impl IntoView {
+    let ascGen = expect_context::<StoredValue<AsciiGenerator>>();
+    // Render in the font the char metrics were measured in so the preview matches the ramp
+    let fontStyle = move || {
+        let family = ascGen.with_value(|g| g.fontFamily(&font()));
+        format!("font-family: '{family}', monospace;")
+    };
+    let asciiArt = move || composition.with(|c| c.flatten().to_string());
+
+    // Edits go to the selected layer, or the live image layer when none is
+    let targetId = move |c: &Composition, selected: Option<u32>| {
+        selected
+            .filter(|&id| c.get(id).is_some())
+            .or_else(|| c.live(LayerKind::Image).map(|l| l.id))
+    };
+    let withTarget = move |f: fn(&Layer) -> bool| {
+        composition.with(|c| {
+            targetId(c, selected())
+                .and_then(|id| c.get(id))
+                .is_some_and(f)
+        })
+    };
+    let updateTarget = move |f: &mut dyn FnMut(&mut Layer)| {
+        let Some(id) = composition.with_untracked(|c| targetId(c, selected.get_untracked())) else {
+            return;
+        };
+        setComposition.update(|c| {
+            if let Some(layer) = c.getMut(id) {
+                f(layer);
+            }
+        });
+    };
+    // Cells are picked on the flattened art, each layer edits at its own offset
+    let editAt = move |x: usize, y: usize, f: &dyn Fn(&mut Canvas, usize, usize)| {
+        updateTarget(&mut |l| {
+            if let Some((lx, ly)) = l.toLocal(x, y) {
+                f(&mut l.canvas, lx, ly);
+            }
+        })
+    };
+    let targetName = move || {
+        composition.with(|c| {
+            let layer = targetId(c, selected()).and_then(|id| c.get(id));
+            layer.map_or("No layer to edit".to_string(), |l| {
+                format!("Editing {}", l.name)
+            })
+        })
+    };
+
+    let (editing, setEditing) = create_signal(false);
+    let (tool, setTool) = create_signal(Tool::default());
+    let selectedTool = Signal::derive(move || tool().id().to_string());
+    let (brush, setBrush) = create_signal("#".to_string());
+    let brushGlyph = move || brush.with_untracked(|b| b.chars().next().unwrap_or(' '));
+    let (drawing, setDrawing) = create_signal(false);
+    // Typed text starts new lines under the column it started at
+    let (cursor, setCursor) = create_signal::<Option<(usize, usize)>>(None);
+    let (textStart, setTextStart) = create_signal(0_usize);
+    let artRef: NodeRef<html::Pre> = create_node_ref();
+
+    // Cell under the pointer, the pre is sized to the art so cells split it evenly
+    let cellAt = move |e: &MouseEvent| {
+        let rect = artRef()?.get_bounding_client_rect();
+        let (w, h) = composition.with_untracked(Composition::size);
+        let x = (e.client_x() as f64 - rect.left()) / rect.width() * w as f64;
+        let y = (e.client_y() as f64 - rect.top()) / rect.height() * h as f64;
+        (x >= 0.0 && y >= 0.0 && (x as usize) < w && (y as usize) < h)
+            .then_some((x as usize, y as usize))
+    };
+
+    let onCanvasDown = move |e: MouseEvent| {
+        if !editing.get_untracked() {
+            return;
+        }
+        let Some((x, y)) = cellAt(&e) else {
+            return;
+        };
+        // Keeps the drag from selecting text, so focus has to be taken by hand
+        e.prevent_default();
+        if let Some(pre) = artRef() {
+            pre.focus().ok();
+        }
+        match tool.get_untracked() {
+            Tool::Paint => {
+                setDrawing(true);
+                let glyph = brushGlyph();
+                updateTarget(&mut |l| l.canvas.beginStroke());
+                editAt(x, y, &|c, x, y| c.paint(x, y, glyph));
+            }
+            Tool::Erase => {
+                setDrawing(true);
+                updateTarget(&mut |l| l.canvas.beginStroke());
+                editAt(x, y, &|c, x, y| c.erase(x, y));
+            }
+            Tool::Fill => {
+                let glyph = brushGlyph();
+                editAt(x, y, &|c, x, y| c.fill(x, y, glyph));
+            }
+            Tool::Text => {
+                setTextStart(x);
+                setCursor(Some((x, y)));
+            }
+        }
+    };
+
+    let onCanvasMove = move |e: MouseEvent| {
+        if !drawing.get_untracked() {
+            return;
+        }
+        if let Some((x, y)) = cellAt(&e) {
+            let glyph = brushGlyph();
+            match tool.get_untracked() {
+                Tool::Erase => editAt(x, y, &|c, x, y| c.erase(x, y)),
+                _ => editAt(x, y, &|c, x, y| c.paint(x, y, glyph)),
+            }
+        }
+    };
+
+    let onCanvasUp = move |_: MouseEvent| {
+        if drawing.get_untracked() {
+            setDrawing(false);
+            updateTarget(&mut |l| l.canvas.endStroke());
+        }
+    };
+
+    let undo = move || {
+        updateTarget(&mut |l| {
+            l.canvas.undo();
+        })
+    };
+    let redo = move || {
+        updateTarget(&mut |l| {
+            l.canvas.redo();
+        })
+    };
+    let onDiscardEdits = move |_: MouseEvent| {
+        logging::log!("Discarding edits...");
+        setCursor(None);
+        updateTarget(&mut Layer::discardEdits);
+    };
+
+    let onCanvasKey = move |e: KeyboardEvent| {
+        if !editing.get_untracked() {
+            return;
+        }
+        let key = e.key();
+        if e.ctrl_key() || e.meta_key() {
+            match key.to_lowercase().as_str() {
+                "z" if e.shift_key() => redo(),
+                "z" => undo(),
+                "y" => redo(),
+                _ => return,
+            }
+            e.prevent_default();
+            return;
+        }
+        let Some((x, y)) = cursor.get_untracked() else {
+            return;
+        };
+        let next = match key.as_str() {
+            "Backspace" => {
+                let x = x.saturating_sub(1);
+                editAt(x, y, &|c, x, y| {
+                    c.typeText(x, y, " ");
+                });
+                (x, y)
+            }
+            "Enter" => (textStart.get_untracked(), y + 1),
+            "ArrowLeft" => (x.saturating_sub(1), y),
+            "ArrowRight" => (x + 1, y),
+            "ArrowUp" => (x, y.saturating_sub(1)),
+            "ArrowDown" => (x, y + 1),
+            text if text.chars().count() == 1 => {
+                editAt(x, y, &|c, x, y| {
+                    c.typeText(x, y, text);
+                });
+                (x + 1, y)
             }
+            _ => return,
+        };
+        e.prevent_default();
+        setCursor(Some(next));
+    };
+
+    let cursorStyle = move || {
+        let (x, y) = cursor()?;
+        let (w, h) = composition.with(Composition::size);
+        (x < w && y < h).then(|| {
+            format!(
+                "left: {}%; top: {}%; width: {}%; height: {}%;",
+                x as f64 * 100.0 / w as f64,
+                y as f64 * 100.0 / h as f64,
+                100.0 / w as f64,
+                100.0 / h as f64
+            )
+        })
+    };
+    let showCursor = move || editing() && tool() == Tool::Text && cursorStyle().is_some();
+
+    let onToolChange = move |e: Event| {
+        let t = Tool::fromId(&event_target_value(&e)).expect("Tool option should be known");
+        setCursor(None);
+        setTool(t);
+    };
+
+    let isDark = move || background() == Background::Dark;
+
+    let (showTimings, setShowTimings) = create_signal(false);
+    let timingRows = move || {
+        timings()
+            .into_iter()
+            .map(|t| {
+                let took = match t.cached {
+                    true => "cached".to_string(),
+                    false => format!("{:.1} ms", t.millis),
+                };
+                view! {
+                    <div class="flex flex-row justify-between space-x-4">
+                        <span>{t.stage}</span>
+                        <span>{took}</span>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+    let totalTime = move || {
+        let total: f64 = timings().iter().map(|t| t.millis).sum();
+        format!("{total:.1} ms")
+    };
+
+    let onThemeChange = move |e: Event| {
+        let bg = if event_target_checked(&e) {
+            Background::Dark
+        } else {
+            Background::Light
         };
-        to_string(&art)
+        logging::log!("Switching to {:?} background...", &bg);
+        setBackground(bg);
     };
 
     view! {
-        <div class="flex-1 h-auto p-8 bg-transparent overflow-auto">
-            <pre class="font-['Courier_New'] text-slate-700 font-mono text-sm leading-4">{asciiArt}</pre>
+        <div class="flex-1 h-auto flex flex-col overflow-hidden">
+            <div class="px-8 py-4 border-b border-amber-500 bg-amber-50 flex flex-row space-x-8">
+                <div class="w-56">
+                    <Toggle
+                        name="darkBackground".to_string()
+                        label="Dark background".to_string()
+                        checked=Signal::derive(isDark)
+                        onInput=onThemeChange
+                    />
+                </div>
+                <div class="w-56">
+                    <Toggle
+                        name="editArt".to_string()
+                        label="Edit art".to_string()
+                        checked=editing
+                        onInput=move |e: Event| setEditing(event_target_checked(&e))
+                    />
+                </div>
+                <div class="w-56">
+                    <Toggle
+                        name="showTimings".to_string()
+                        label="Show timings".to_string()
+                        onInput=move |e: Event| setShowTimings(event_target_checked(&e))
+                    />
+                </div>
+            </div>
+            <Show when=editing>
+                <div class="px-8 py-2 border-b border-amber-500 bg-amber-50 flex flex-row items-center space-x-8 text-sm text-zinc-800">
+                    <div class="w-40">
+                        <Select
+                            name="tool".to_string()
+                            label="Tool".to_string()
+                            options=Tool::options()
+                            value=selectedTool
+                            onChange=onToolChange
+                        />
+                    </div>
+                    <label class="flex flex-row items-center space-x-2">
+                        <span>Char</span>
+                        <input
+                            type="text"
+                            maxlength="1"
+                            class="w-8 p-1 rounded-sm bg-transparent border border-amber-500 font-mono text-center"
+                            prop:value=brush
+                            on:input=move |e| setBrush(event_target_value(&e))
+                        />
+                    </label>
+                    <span class="font-medium">{targetName}</span>
+                    <button
+                        disabled=move || !withTarget(|l| l.canvas.canUndo())
+                        class="disabled:opacity-40"
+                        on:click=move |_| undo()
+                    >
+                        Undo
+                    </button>
+                    <button
+                        disabled=move || !withTarget(|l| l.canvas.canRedo())
+                        class="disabled:opacity-40"
+                        on:click=move |_| redo()
+                    >
+                        Redo
+                    </button>
+                    <button on:click=onDiscardEdits>Discard edits</button>
+                    <Show when=move || withTarget(Layer::isStale)>
+                        <span class="text-red-600">
+                            Newer art is waiting behind these edits, discard them to see it
+                        </span>
+                    </Show>
+                </div>
+            </Show>
+            <div class="relative flex-1 flex flex-col overflow-hidden">
+                <div class="flex-1 p-8 bg-transparent overflow-auto" class:bg-slate-900=isDark>
+                    <div class="relative w-fit">
+                        <pre
+                            class="text-sm leading-4 outline-none"
+                            class:text-slate-700=move || !isDark()
+                            class:text-amber-50=isDark
+                            class:cursor-crosshair=editing
+                            class:select-none=editing
+                            style=fontStyle
+                            tabindex="0"
+                            node_ref=artRef
+                            on:mousedown=onCanvasDown
+                            on:mousemove=onCanvasMove
+                            on:mouseup=onCanvasUp
+                            on:mouseleave=onCanvasUp
+                            on:keydown=onCanvasKey
+                        >
+                            {asciiArt}
+                        </pre>
+                        <Show when=showCursor>
+                            <div
+                                class="absolute bg-cyan-600/40 pointer-events-none"
+                                style=cursorStyle
+                            ></div>
+                        </Show>
+                    </div>
+                </div>
+                <Show when=showTimings>
+                    <div class="absolute top-2 right-2 p-2 rounded-sm border border-amber-500 bg-amber-50/90 text-xs text-zinc-800">
+                        {timingRows}
+                        <div class="flex flex-row justify-between space-x-4 font-medium">
+                            <span>Total</span>
+                            <span>{totalTime}</span>
+                        </div>
+                    </div>
+                </Show>
+            </div>
         </div>
     }
 }
@@ -0,0 +1,76 @@
+use leptos::*;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{Event, FileReader, HtmlInputElement};
+
+use crate::{asciiArt::AsciiArt, components::textfield::*, figlet::FigFont};
+
+#[component]
+pub fn BannerText(setBanner: WriteSignal<Option<AsciiArt>>) -> impl IntoView {
+    let (font, setFont) = create_signal::<Option<FigFont>>(None);
+    let (fontName, setFontName) = create_signal::<Option<String>>(None);
+    let (fontError, setFontError) = create_signal::<Option<String>>(None);
+    let (text, setText) = create_signal(String::new());
+
+    // Feeds the live banner layer, which is placed from the layer list
+    create_effect(move |_| {
+        let banner = font.with(|f| {
+            let art = text.with(|t| f.as_ref().map(|f| f.render(t)));
+            art.filter(|a| !a.isEmpty())
+        });
+        setBanner(banner);
+    });
+
+    let onFontUpload = move |e: Event| {
+        let input = event_target::<HtmlInputElement>(&e);
+        let Some(file) = input.files().and_then(|f| f.get(0)) else {
+            return;
+        };
+        logging::log!("Loading banner font {}...", file.name());
+        let name = file.name();
+        let reader = FileReader::new().expect("Failed to create file reader");
+        let frc = reader.clone();
+        let onloadClosure = Closure::wrap(Box::new(move || {
+            let flf = frc
+                .result()
+                .ok()
+                .and_then(|r| r.as_string())
+                .unwrap_or_default();
+            match FigFont::parse(&flf) {
+                Ok(f) => {
+                    setFontError(None);
+                    setFontName(Some(name.clone()));
+                    setFont(Some(f));
+                }
+                Err(err) => setFontError(Some(err.to_string())),
+            }
+        }) as Box<dyn FnMut()>);
+
+        reader
+            .read_as_text(&file)
+            .expect("Failed to load font into buffer");
+        reader.set_onload(Some(onloadClosure.as_ref().unchecked_ref()));
+        onloadClosure.forget();
+    };
+
+    let fontLabel = move || fontName().unwrap_or_else(|| "Load FIGlet font (.flf)".to_string());
+
+    view! {
+        <div class="space-y-4">
+            <span class="text-xl font-medium text-zinc-800">Banner</span>
+            <label class="block w-full p-2 rounded-sm border border-amber-500 text-sm font-medium text-center text-zinc-800 cursor-pointer hover:bg-amber-100">
+                {fontLabel}
+                <input type="file" accept=".flf" class="hidden" on:change=onFontUpload/>
+            </label>
+            <p class="text-sm text-red-600">{fontError}</p>
+            <Show when=move || font.with(Option::is_some)>
+                <TextField
+                    name="bannerText".to_string()
+                    label="Text".to_string()
+                    value=text
+                    error=Signal::derive(|| None)
+                    onInput=move |e: Event| setText(event_target_value(&e))
+                />
+            </Show>
+        </div>
+    }
+}
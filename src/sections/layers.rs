@@ -0,0 +1,170 @@
+use leptos::*;
+use web_sys::{Event, MouseEvent};
+
+use crate::{
+    asciiArt::AsciiArt,
+    composition::{Composition, Layer, LayerKind},
+};
+
+// Size of a blank layer when there's nothing to match it to yet
+const BLANK_LAYER_SIZE: (usize, usize) = (80, 24);
+
+#[component]
+pub fn Layers(
+    composition: ReadSignal<Composition>,
+    setComposition: WriteSignal<Composition>,
+    selected: ReadSignal<Option<u32>>,
+    setSelected: WriteSignal<Option<u32>>,
+) -> impl IntoView {
+    let updateLayer = move |id: u32, f: &dyn Fn(&mut Layer)| {
+        setComposition.update(|c| {
+            if let Some(layer) = c.getMut(id) {
+                f(layer);
+            }
+        })
+    };
+
+    let layerRows = move || {
+        // Top layer first, the order they stack in on the art
+        composition.with(|c| {
+            c.layers()
+                .iter()
+                .rev()
+                .map(|layer| {
+                    let (id, live) = (layer.id, layer.live);
+                    let isSelected = move || selected() == Some(id);
+                    let name = match live {
+                        true => format!("{} (live)", layer.name),
+                        false => layer.name.clone(),
+                    };
+                    let onSelect = move |_: MouseEvent| {
+                        setSelected(if isSelected() { None } else { Some(id) })
+                    };
+                    let onVisibleChange = move |e: Event| {
+                        let visible = event_target_checked(&e);
+                        updateLayer(id, &|l| l.visible = visible);
+                    };
+                    let onTransparentChange = move |e: Event| {
+                        let transparent = event_target_checked(&e);
+                        updateLayer(id, &|l| l.spaceTransparent = transparent);
+                    };
+                    let onColumnChange = move |e: Event| {
+                        let column = event_target_value(&e).parse().unwrap_or(0);
+                        updateLayer(id, &|l| l.column = column);
+                    };
+                    let onRowChange = move |e: Event| {
+                        let row = event_target_value(&e).parse().unwrap_or(0);
+                        updateLayer(id, &|l| l.row = row);
+                    };
+                    let onRaise =
+                        move |_: MouseEvent| setComposition.update(|c| c.moveLayer(id, true));
+                    let onLower =
+                        move |_: MouseEvent| setComposition.update(|c| c.moveLayer(id, false));
+                    let onKeep = move |_: MouseEvent| {
+                        logging::log!("Keeping layer {id}...");
+                        setComposition.update(|c| c.keep(id));
+                    };
+                    let onRemove = move |_: MouseEvent| {
+                        logging::log!("Removing layer {id}...");
+                        if isSelected() {
+                            setSelected(None);
+                        }
+                        setComposition.update(|c| c.remove(id));
+                    };
+                    view! {
+                        <div
+                            class="p-2 space-y-2 rounded-sm border text-sm text-zinc-800"
+                            class:border-amber-500=move || !isSelected()
+                            class:border-cyan-600=isSelected
+                        >
+                            <div class="flex flex-row justify-between">
+                                <button
+                                    class="truncate font-medium hover:underline"
+                                    title="Select for editing"
+                                    on:click=onSelect
+                                >
+                                    {name}
+                                </button>
+                                <div class="flex flex-row space-x-3">
+                                    <button title="Raise" on:click=onRaise>"↑"</button>
+                                    <button title="Lower" on:click=onLower>"↓"</button>
+                                    <button title="Remove" on:click=onRemove>"✕"</button>
+                                </div>
+                            </div>
+                            <div class="flex flex-row space-x-4">
+                                <label class="flex flex-row items-center space-x-1">
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=layer.visible
+                                        on:change=onVisibleChange
+                                    />
+                                    <span>Visible</span>
+                                </label>
+                                <label class="flex flex-row items-center space-x-1">
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=layer.spaceTransparent
+                                        on:change=onTransparentChange
+                                    />
+                                    <span>Spaces see through</span>
+                                </label>
+                            </div>
+                            <div class="flex flex-row items-center space-x-2">
+                                <span>Column</span>
+                                <input
+                                    type="number"
+                                    min="0"
+                                    class="w-16 p-1 rounded-sm bg-transparent border border-amber-500"
+                                    prop:value=layer.column.to_string()
+                                    on:change=onColumnChange
+                                />
+                                <span>Row</span>
+                                <input
+                                    type="number"
+                                    min="0"
+                                    class="w-16 p-1 rounded-sm bg-transparent border border-amber-500"
+                                    prop:value=layer.row.to_string()
+                                    on:change=onRowChange
+                                />
+                            </div>
+                            {live
+                                .then(|| {
+                                    view! {
+                                        <button class="hover:underline" on:click=onKeep>
+                                            Keep, later art starts a new layer
+                                        </button>
+                                    }
+                                })}
+                        </div>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    let onAddLayer = move |_: MouseEvent| {
+        logging::log!("Adding blank layer...");
+        let mut added = None;
+        setComposition.update(|c| {
+            let (w, h) = match c.size() {
+                (0, _) | (_, 0) => BLANK_LAYER_SIZE,
+                size => size,
+            };
+            added = Some(c.add(LayerKind::Drawing, AsciiArt::new(w, h)));
+        });
+        setSelected(added);
+    };
+
+    view! {
+        <div class="space-y-4">
+            <span class="text-xl font-medium text-zinc-800">Layers</span>
+            <div class="space-y-2">{layerRows}</div>
+            <button
+                class="w-full p-2 rounded-sm border border-amber-500 text-sm font-medium text-zinc-800 hover:bg-amber-100"
+                on:click=onAddLayer
+            >
+                Add blank layer
+            </button>
+        </div>
+    }
+}
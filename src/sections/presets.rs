@@ -0,0 +1,128 @@
+use leptos::*;
+use web_sys::{Event, MouseEvent};
+
+use crate::{
+    asciiGenerator::AsciiGenerator,
+    components::{select::*, textfield::*},
+    settings::{loadPresets, savePresets, ConversionSettings, SettingsFormat},
+};
+
+#[component]
+pub fn Presets(
+    settings: Memo<ConversionSettings>,
+    applySettings: Callback<ConversionSettings>,
+) -> impl IntoView {
+    let ascGen = expect_context::<StoredValue<AsciiGenerator>>();
+    let (presets, setPresets) = create_signal(ascGen.with_value(loadPresets));
+    let (presetName, setPresetName) = create_signal(String::new());
+    let (nameError, setNameError) = create_signal::<Option<String>>(None);
+
+    let onNameInput = move |e: Event| {
+        setPresetName(event_target_value(&e));
+        setNameError(None);
+    };
+
+    let onSavePreset = move |_: MouseEvent| {
+        let name = presetName().trim().to_string();
+        if name.is_empty() {
+            setNameError(Some("Give the preset a name".to_string()));
+            return;
+        }
+        logging::log!("Saving preset {}...", &name);
+        // Saving under an existing name overwrites it
+        setPresets.update(|p| {
+            p.insert(name, settings.get_untracked());
+            savePresets(p);
+        });
+        setPresetName(String::new());
+    };
+
+    let presetRows = move || {
+        presets()
+            .into_iter()
+            .map(|(name, preset)| {
+                let removed = name.clone();
+                let onLoad = move |_: MouseEvent| applySettings(preset.clone());
+                let onDelete = move |_: MouseEvent| {
+                    logging::log!("Deleting preset {}...", &removed);
+                    setPresets.update(|p| {
+                        p.remove(&removed);
+                        savePresets(p);
+                    });
+                };
+                view! {
+                    <div class="flex flex-row justify-between text-sm text-zinc-800">
+                        <button class="truncate hover:underline" title="Load" on:click=onLoad>
+                            {name}
+                        </button>
+                        <button title="Delete" on:click=onDelete>"✕"</button>
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    let (format, setFormat) = create_signal(SettingsFormat::Json);
+    let selectedFormat = Signal::derive(move || format().id().to_string());
+    let (text, setText) = create_signal(String::new());
+    let (importError, setImportError) = create_signal::<Option<String>>(None);
+
+    let onFormatChange = move |e: Event| {
+        let f =
+            SettingsFormat::fromId(&event_target_value(&e)).expect("Format option should be known");
+        setFormat(f);
+    };
+
+    let onExport = move |_: MouseEvent| {
+        setImportError(None);
+        setText(settings.with_untracked(|s| s.export(format())));
+    };
+
+    let onImport = move |_: MouseEvent| match ascGen
+        .with_value(|g| ConversionSettings::import(&text(), format(), g))
+    {
+        Ok(imported) => {
+            setImportError(None);
+            applySettings(imported);
+        }
+        Err(err) => setImportError(Some(err)),
+    };
+
+    view! {
+        <div class="space-y-4">
+            <span class="text-xl font-medium text-zinc-800">Presets</span>
+            {presetRows}
+            <TextField
+                name="presetName".to_string()
+                label="Name".to_string()
+                value=presetName
+                error=nameError
+                onInput=onNameInput
+            />
+            <button
+                class="w-full p-2 rounded-sm border border-amber-500 text-sm font-medium text-zinc-800 hover:bg-amber-100"
+                on:click=onSavePreset
+            >
+                Save preset
+            </button>
+            <Select
+                name="settingsFormat".to_string()
+                label="Settings".to_string()
+                options=SettingsFormat::options()
+                value=selectedFormat
+                onChange=onFormatChange
+            />
+            <textarea
+                class="w-full h-40 p-2 rounded-sm bg-transparent border border-amber-500 font-mono text-xs"
+                class:border-red-600=move || importError().is_some()
+                prop:value=text
+                on:input=move |e| setText(event_target_value(&e))
+            ></textarea>
+            <p class="text-sm text-red-600">{importError}</p>
+            <div class="flex flex-row justify-between text-sm text-zinc-800">
+                <button on:click=onExport>Export</button>
+                <button on:click=onImport>Import</button>
+            </div>
+        </div>
+    }
+}
@@ -6,80 +6,360 @@ use image::{
 use leptos::*;
 use regex::Regex;
 use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    js_sys::{ArrayBuffer, Uint8Array},
-    Event, FileReader, HtmlInputElement, MouseEvent, Url,
+    js_sys::{Array, ArrayBuffer, Uint8Array},
+    Blob, ClipboardEvent, Event, FileReader, HtmlInputElement, MouseEvent, Response, Url,
 };
 
 use crate::{
-    components::{select::*, slider::*, toggle::*, upload::*},
-    imageHandler::ImageHandler,
+    adjustments::{Adjustment, Pipeline},
+    asciiArt::AsciiArt,
+    asciiGenerator::AsciiGenerator,
+    components::{select::*, slider::*, textfield::*, toggle::*, upload::*},
+    composition::Composition,
+    imageHandler::{
+        loadImage, parseHexColor, toHexColor, Fit, ImageHandler, LoadError, OutputSize, Tone,
+        Transparency, ACCEPTED_TYPES, MAX_COLUMNS, MAX_FILE_BYTES, MAX_ROWS,
+    },
+    ramp::RampSource,
+    sections::{adjustments::*, banner::*, layers::*, presets::*},
+    session,
+    settings::ConversionSettings,
 };
 
 #[component]
 pub fn Sidebar(
+    settings: Memo<ConversionSettings>,
+    applySettings: Callback<ConversionSettings>,
     setImage: WriteSignal<Option<DynamicImage>>,
-    gray: ReadSignal<Option<GrayImage>>,
-    setGray: WriteSignal<Option<GrayImage>>,
-    setResizedImage: WriteSignal<Option<GrayImage>>,
-    setContrast: WriteSignal<f32>,
-    setBrightness: WriteSignal<i32>,
+    transparency: ReadSignal<Transparency>,
+    setTransparency: WriteSignal<Transparency>,
+    size: ReadSignal<OutputSize>,
+    setSize: WriteSignal<OutputSize>,
+    grid: Signal<Option<(u32, u32)>>,
+    pipeline: ReadSignal<Pipeline>,
+    setPipeline: WriteSignal<Pipeline>,
+    dither: ReadSignal<bool>,
     setDither: WriteSignal<bool>,
+    tone: ReadSignal<Tone>,
+    setTone: WriteSignal<Tone>,
+    font: ReadSignal<String>,
+    setFont: WriteSignal<String>,
+    rampSource: ReadSignal<RampSource>,
+    setRampSource: WriteSignal<RampSource>,
+    setBanner: WriteSignal<Option<AsciiArt>>,
+    composition: ReadSignal<Composition>,
+    setComposition: WriteSignal<Composition>,
+    selected: ReadSignal<Option<u32>>,
+    setSelected: WriteSignal<Option<u32>>,
 ) -> impl IntoView {
+    let ascGen = expect_context::<StoredValue<AsciiGenerator>>();
     let uploadRef: NodeRef<html::Input> = create_node_ref();
 
     let (imageUrl, setImageUrl) = create_signal::<Option<String>>(None);
+    // Object urls keep the file alive until they're revoked
+    let setPreview = move |url: Option<String>| {
+        if let Some(old) = imageUrl.get_untracked() {
+            Url::revoke_object_url(&old).ok();
+        }
+        setImageUrl(url);
+    };
+    let showImage = move |bytes: &[u8], img: DynamicImage| {
+        let parts = Array::of1(&Uint8Array::from(bytes));
+        let blob = Blob::new_with_u8_array_sequence(&parts).expect("Failed to create blob");
+        setPreview(Url::create_object_url_with_blob(&blob).ok());
+        setImage(Some(img));
+    };
 
-    let displayImage = move || {
-        imageUrl().map(|_| {
-            // TODO: Add option to delete image and start over
-            Some(
-                view! { <img src=imageUrl alt="Unable to display uploaded image" class="w-full"/> },
+    // Pick up the image from last session, unless one was uploaded in the meantime
+    spawn_local(async move {
+        let Some(bytes) = session::loadImageBytes().await else {
+            return;
+        };
+        if imageUrl.get_untracked().is_some() {
+            return;
+        }
+        match loadImage(&bytes) {
+            Ok(img) => {
+                logging::log!("Restoring image from last session...");
+                showImage(&bytes, img);
+            }
+            Err(err) => logging::warn!("Failed to restore image: {err}"),
+        }
+    });
+
+    let previewRef: NodeRef<html::Div> = create_node_ref();
+    let (dragStart, setDragStart) = create_signal::<Option<(f32, f32)>>(None);
+    let (selection, setSelection) = create_signal::<Option<(f32, f32, f32, f32)>>(None);
+
+    // Pointer position as a fraction of the preview image
+    let pointerPos = move |e: &MouseEvent| {
+        let rect = previewRef()
+            .expect("Preview should be mounted")
+            .get_bounding_client_rect();
+        let x = (e.client_x() as f64 - rect.left()) / rect.width();
+        let y = (e.client_y() as f64 - rect.top()) / rect.height();
+        (x.clamp(0.0, 1.0) as f32, y.clamp(0.0, 1.0) as f32)
+    };
+
+    let onCropStart = move |e: MouseEvent| {
+        e.prevent_default();
+        let (x, y) = pointerPos(&e);
+        setDragStart(Some((x, y)));
+        setSelection(Some((x, y, 0.0, 0.0)));
+    };
+
+    let onCropMove = move |e: MouseEvent| {
+        if let Some((x0, y0)) = dragStart() {
+            let (x1, y1) = pointerPos(&e);
+            setSelection(Some((
+                x0.min(x1),
+                y0.min(y1),
+                (x1 - x0).abs(),
+                (y1 - y0).abs(),
+            )));
+        }
+    };
+
+    let onCropEnd = move |_: MouseEvent| {
+        if dragStart.get_untracked().is_none() {
+            return;
+        }
+        setDragStart(None);
+        // A click without a drag clears the crop
+        let crop = selection
+            .get_untracked()
+            .filter(|&(_, _, w, h)| w > 0.02 && h > 0.02)
+            .map(|(left, top, width, height)| Adjustment::Crop {
+                left,
+                top,
+                width,
+                height,
+            });
+        if crop.is_none() {
+            setSelection(None);
+        }
+        logging::log!("Cropping to {:?}...", &crop);
+        setPipeline.update(|p| p.setCrop(crop));
+    };
+
+    let onResetCrop = move |_: MouseEvent| {
+        setSelection(None);
+        setPipeline.update(|p| p.setCrop(None));
+    };
+
+    let onStartOver = move |_: MouseEvent| {
+        logging::log!("Starting over...");
+        spawn_local(session::clear());
+        setSelection(None);
+        setPreview(None);
+        setImage(None);
+        applySettings(ConversionSettings::default());
+    };
+
+    let onRemoveImage = move |_: MouseEvent| {
+        logging::log!("Removing image...");
+        spawn_local(session::deleteImageBytes());
+        setSelection(None);
+        setPreview(None);
+        setImage(None);
+    };
+
+    let (keepSettings, setKeepSettings) = create_signal(session::loadKeepSettings());
+
+    let onKeepSettingsChange = move |e: Event| {
+        let keep = event_target_checked(&e);
+        session::saveKeepSettings(keep);
+        setKeepSettings(keep);
+    };
+
+    let addStep = move |step: Adjustment| {
+        logging::log!("Adding {} adjustment...", step.label());
+        setPipeline.update(|p| p.steps.push(step));
+    };
+
+    let selectionStyle = move || {
+        selection().map(|(x, y, w, h)| {
+            format!(
+                "left: {}%; top: {}%; width: {}%; height: {}%;",
+                x * 100.0,
+                y * 100.0,
+                w * 100.0,
+                h * 100.0
             )
         })
     };
 
-    let onImageUpload = move |_| {
-        logging::log!("Uploading new image...");
-        let file = uploadRef()
-            .unwrap()
-            .files()
-            .unwrap()
-            .get(0)
-            .expect("No image uploaded");
+    let (openError, setOpenError) = create_signal::<Option<String>>(None);
 
-        setImageUrl(Url::create_object_url_with_blob(&file).ok());
+    // Uploads, drops, pastes and links all end up here
+    let openImage = move |bytes: Vec<u8>| match loadImage(&bytes) {
+        Ok(img) => {
+            setOpenError(None);
+            // Only replacing an image resets, the first one keeps settings from a link or session
+            if !keepSettings.get_untracked() && imageUrl.get_untracked().is_some() {
+                setSelection(None);
+                applySettings(ConversionSettings::default());
+            }
+            showImage(&bytes, img);
+            spawn_local(session::saveImageBytes(bytes));
+        }
+        Err(err) => setOpenError(Some(err.to_string())),
+    };
 
+    let readFile = move |file: &Blob| {
+        // Checked before reading so a huge file never makes it into memory
+        let size = file.size() as usize;
+        if size > MAX_FILE_BYTES {
+            setOpenError(Some(LoadError::TooLarge(size).to_string()));
+            return;
+        }
         let reader = FileReader::new().expect("Failed to create file reader");
         let frc = reader.clone();
         let onloadClosure = Closure::wrap(Box::new(move || {
             let arrayBuffer = frc.result().expect("Failed to read buffer");
             let uint8Array = Uint8Array::new(&arrayBuffer);
-            let bytes: Vec<u8> = uint8Array.to_vec();
-
-            let img = image::load_from_memory(&bytes)
-                .expect("Failed to convert Uint8Array to DynamicImage");
-
-            // TODO: Plugin filter states to modify image. Reset filter states when a new image is uploaded
-            // image.set(Some(img.resize(nw, nh, FilterType::Nearest).clone()));
-            let gray = img.into_luma8();
-            setGray(Some(gray.clone()));
-
-            let (w, h) = gray.dimensions();
-            let nw = 700;
-            let nh = nw * h / w;
-            setResizedImage(Some(
-                resize(&gray, nw, nh, FilterType::Nearest).downsample(),
-            ));
+            openImage(uint8Array.to_vec());
         }) as Box<dyn FnMut()>);
 
         reader
-            .read_as_array_buffer(&file)
+            .read_as_array_buffer(file)
             .expect("Failed to load image into buffer");
         reader.set_onload(Some(onloadClosure.as_ref().unchecked_ref()));
         onloadClosure.forget();
     };
 
+    let onImageUpload = move |e: Event| {
+        logging::log!("Uploading new image...");
+        let input = event_target::<HtmlInputElement>(&e);
+        let file = input
+            .files()
+            .expect("Upload input should have files")
+            .get(0)
+            .expect("No image uploaded");
+        // Cleared so picking the same file again still fires a change
+        input.set_value("");
+        readFile(&file);
+    };
+
+    // The whole page takes dropped and pasted images, not just the upload button
+    let (dragging, setDragging) = create_signal(false);
+    let dragOverHandle = window_event_listener(ev::dragover, move |e| {
+        e.prevent_default();
+        setDragging(true);
+    });
+    let dragLeaveHandle = window_event_listener(ev::dragleave, move |e| {
+        // Moving between elements also fires this, only leaving the page has nothing to enter
+        if e.related_target().is_none() {
+            setDragging(false);
+        }
+    });
+    let dropHandle = window_event_listener(ev::drop, move |e| {
+        e.prevent_default();
+        setDragging(false);
+        match e
+            .data_transfer()
+            .and_then(|d| d.files())
+            .and_then(|f| f.get(0))
+        {
+            Some(file) => {
+                logging::log!("Opening dropped image...");
+                readFile(&file);
+            }
+            None => setOpenError(Some("Only image files can be dropped".to_string())),
+        }
+    });
+    let pasteHandle = window_event_listener(ev::paste, move |e| {
+        let e: ClipboardEvent = e.unchecked_into();
+        // Text pastes are left alone so the inputs keep working
+        let file = e
+            .clipboard_data()
+            .and_then(|d| d.files())
+            .and_then(|f| f.get(0));
+        if let Some(file) = file.filter(|f| f.type_().starts_with("image/")) {
+            e.prevent_default();
+            logging::log!("Opening pasted image...");
+            readFile(&file);
+        }
+    });
+    on_cleanup(move || {
+        dragOverHandle.remove();
+        dragLeaveHandle.remove();
+        dropHandle.remove();
+        pasteHandle.remove();
+    });
+
+    let (imageLink, setImageLink) = create_signal(String::new());
+    let (linkError, setLinkError) = create_signal::<Option<String>>(None);
+
+    let onImageLinkInput = move |e: Event| {
+        setImageLink(event_target_value(&e));
+        setLinkError(None);
+    };
+
+    let onLoadLink = move |_: MouseEvent| {
+        let link = imageLink().trim().to_string();
+        logging::log!("Loading image from {}...", &link);
+        spawn_local(async move {
+            match fetchImage(&link).await {
+                Ok(bytes) => openImage(bytes),
+                Err(err) => setLinkError(Some(err)),
+            }
+        });
+    };
+
+    let displayImage = move || {
+        imageUrl().map(|_| {
+            view! {
+                <div
+                    class="relative cursor-crosshair select-none"
+                    node_ref=previewRef
+                    on:mousedown=onCropStart
+                    on:mousemove=onCropMove
+                    on:mouseup=onCropEnd
+                    on:mouseleave=onCropEnd
+                >
+                    <img
+                        src=imageUrl
+                        alt="Unable to display uploaded image"
+                        class="w-full"
+                        draggable="false"
+                    />
+                    <Show when=move || selection().is_some()>
+                        <div
+                            class="absolute border-2 border-cyan-600 bg-cyan-600/10 pointer-events-none"
+                            style=selectionStyle
+                        ></div>
+                    </Show>
+                </div>
+                <div class="px-8 pt-4 flex flex-row justify-between text-sm text-zinc-800">
+                    <button on:click=move |_| addStep(Adjustment::Rotate { quarterTurns: 3 })>
+                        Rotate left
+                    </button>
+                    <button on:click=move |_| addStep(Adjustment::Rotate { quarterTurns: 1 })>
+                        Rotate right
+                    </button>
+                    <button on:click=move |_| addStep(Adjustment::FlipHorizontal)>Flip</button>
+                    <button on:click=onResetCrop>Reset crop</button>
+                </div>
+                <div class="px-8 pt-2 flex flex-row justify-between text-sm text-zinc-800">
+                    <label class="cursor-pointer">
+                        Replace
+                        <input
+                            type="file"
+                            accept=ACCEPTED_TYPES
+                            class="hidden"
+                            on:change=onImageUpload
+                        />
+                    </label>
+                    <button on:click=onRemoveImage>Remove</button>
+                    <button on:click=onStartOver>Start over</button>
+                </div>
+            }
+        })
+    };
+
     let displayInput = move || {
         imageUrl().map_or_else(
             || Some(view! { <Upload onUpload=onImageUpload inputRef=uploadRef/> }),
@@ -87,42 +367,112 @@ pub fn Sidebar(
         )
     };
 
-    let onContrastChange = move |e: Event| {
-        // TODO: Adjust contrast inplace instead of creating new image
-        let c: f32 = event_target_value(&e)
-            .parse()
-            .expect("Contrast should be a number");
-        logging::log!("Adjusting contrast to {:?}...", &c);
-        setContrast(c);
+    // Both dimensions are kept so switching modes doesn't lose the other one
+    let (columns, setColumns) = create_signal::<u32>(100);
+    let (rows, setRows) = create_signal::<u32>(50);
+    let (fit, setFit) = create_signal(Fit::default());
+    // Loaded settings can change the size without going through the controls
+    create_effect(move |_| match size() {
+        OutputSize::Columns(c) => setColumns(c),
+        OutputSize::Rows(r) => setRows(r),
+        OutputSize::Box { columns, rows, fit } => batch(|| {
+            setColumns(columns);
+            setRows(rows);
+            setFit(fit);
+        }),
+    });
+    let sizeModes = vec![
+        ("columns".to_string(), "Columns".to_string()),
+        ("rows".to_string(), "Rows".to_string()),
+        ("box".to_string(), "Box".to_string()),
+    ];
+    let sizeMode = Signal::derive(move || match size() {
+        OutputSize::Columns(_) => "columns".to_string(),
+        OutputSize::Rows(_) => "rows".to_string(),
+        OutputSize::Box { .. } => "box".to_string(),
+    });
+
+    let applySize = move |mode: &str| {
+        let s = match mode {
+            "rows" => OutputSize::Rows(rows()),
+            "box" => OutputSize::Box {
+                columns: columns(),
+                rows: rows(),
+                fit: fit(),
+            },
+            _ => OutputSize::Columns(columns()),
+        };
+        logging::log!("Resizing to {:?}...", &s);
+        setSize(s);
     };
 
-    let onBrightnessChange = move |e: Event| {
-        // TODO: Adjust brightness in place instead of creating a new image
-        let b: i32 = event_target_value(&e)
+    let onSizeModeChange = move |e: Event| applySize(&event_target_value(&e));
+
+    let onColumnsChange = move |e: Event| {
+        let c: u32 = event_target_value(&e)
             .parse()
-            .expect("Contrast should be a number");
-        logging::log!("Adjusting brightness to {:?}...", &b);
-        setBrightness(b);
+            .expect("Columns should be a number");
+        setColumns(c);
+        applySize(&sizeMode.get_untracked());
     };
 
-    let onSizeChange = move |e: Event| {
-        let mut nw: u32 = event_target_value(&e)
+    let onRowsChange = move |e: Event| {
+        let r: u32 = event_target_value(&e)
             .parse()
-            .expect("Contrast should be a number");
-        nw *= 7;
-        let (w, h) = gray().expect("Gray image should be set").dimensions();
-        let nh = nw * h / w;
-        let filter = if nw > w {
-            FilterType::Lanczos3
-        } else {
-            FilterType::Triangle
+            .expect("Rows should be a number");
+        setRows(r);
+        applySize(&sizeMode.get_untracked());
+    };
+
+    let onFitChange = move |e: Event| {
+        setFit(Fit::fromId(&event_target_value(&e)).expect("Fit option should be known"));
+        applySize(&sizeMode.get_untracked());
+    };
+
+    let selectedFit = Signal::derive(move || fit().id().to_string());
+
+    let gridLabel = move || grid().map(|(c, r)| format!("{c} × {r}"));
+
+    let selectedTone = Signal::derive(move || tone().id().to_string());
+
+    let onToneChange = move |e: Event| {
+        let t = Tone::fromId(&event_target_value(&e)).expect("Tone option should be known");
+        logging::log!("Switching tone to {:?}...", &t);
+        setTone(t);
+    };
+
+    let transparencyOptions = vec![
+        ("composite".to_string(), "Fill with color".to_string()),
+        ("blank".to_string(), "Leave blank".to_string()),
+    ];
+    let selectedTransparency = Signal::derive(move || match transparency() {
+        Transparency::Composite(_) => "composite".to_string(),
+        Transparency::Blank => "blank".to_string(),
+    });
+    // Remembered so switching to blank and back keeps the picked color
+    let (fillColor, setFillColor) = create_signal(match transparency.get_untracked() {
+        Transparency::Composite(color) => color,
+        Transparency::Blank => [255, 255, 255],
+    });
+    create_effect(move |_| {
+        if let Transparency::Composite(color) = transparency() {
+            setFillColor(color);
+        }
+    });
+
+    let onTransparencyChange = move |e: Event| {
+        let t = match event_target_value(&e).as_str() {
+            "blank" => Transparency::Blank,
+            _ => Transparency::Composite(fillColor()),
         };
-        logging::log!("Resizing to {} x {}...", nw, nh);
-        setResizedImage(
-            gray()
-                .as_ref()
-                .map(|img| resize(img, nw, nh, filter).downsample()),
-        );
+        logging::log!("Switching transparency to {:?}...", &t);
+        setTransparency(t);
+    };
+
+    let onFillColorInput = move |e: Event| {
+        let color = parseHexColor(&event_target_value(&e)).expect("Color input should give hex");
+        setFillColor(color);
+        setTransparency(Transparency::Composite(color));
     };
 
     let onDitherChange = move |e: Event| {
@@ -135,42 +485,265 @@ pub fn Sidebar(
         setDither(c);
     };
 
+    let presetChars = move |id: &str| {
+        ascGen.with_value(|g| {
+            g.presets()
+                .iter()
+                .find(|p| p.id == id)
+                .map(|p| p.chars.clone())
+                .unwrap_or_default()
+        })
+    };
+    let rampOptions: Vec<(String, String)> = ascGen.with_value(|g| {
+        g.presets()
+            .iter()
+            .map(|p| (p.id.clone(), p.name.clone()))
+            .chain([("custom".to_string(), "Custom".to_string())])
+            .collect()
+    });
+    let selectedRamp = Signal::derive(move || match rampSource() {
+        RampSource::Preset(id) => id,
+        RampSource::Custom(_) => "custom".to_string(),
+    });
+    let (customChars, setCustomChars) = create_signal(match rampSource.get_untracked() {
+        RampSource::Preset(id) => presetChars(&id),
+        RampSource::Custom(chars) => chars,
+    });
+    let (charsetError, setCharsetError) = create_signal::<Option<String>>(None);
+    create_effect(move |_| {
+        if let RampSource::Custom(chars) = rampSource() {
+            if chars != customChars.get_untracked() {
+                setCustomChars(chars);
+                setCharsetError(None);
+            }
+        }
+    });
+
+    let applyCustomChars =
+        move |chars: String| match ascGen.with_value(|g| g.validateCharset(&font(), &chars)) {
+            Ok(()) => {
+                setCharsetError(None);
+                setRampSource(RampSource::Custom(chars));
+            }
+            Err(err) => setCharsetError(Some(err.to_string())),
+        };
+
+    let onRampChange = move |e: Event| {
+        let id = event_target_value(&e);
+        logging::log!("Switching ramp to {}...", &id);
+        if id == "custom" {
+            applyCustomChars(customChars());
+        } else {
+            setCustomChars(presetChars(&id));
+            setCharsetError(None);
+            setRampSource(RampSource::Preset(id));
+        }
+    };
+
+    let (rampLength, setRampLength) = create_signal::<usize>(12);
+
+    let onRampLengthChange = move |e: Event| {
+        let n: usize = event_target_value(&e)
+            .parse()
+            .expect("Ramp length should be a number");
+        setRampLength(n);
+    };
+
+    let onGenerateRamp = move |_: MouseEvent| {
+        let chars = ascGen.with_value(|g| g.optimalCharset(&font(), rampLength()));
+        logging::log!("Generated ramp {:?}", &chars);
+        setCustomChars(chars.clone());
+        applyCustomChars(chars);
+    };
+
+    let fontOptions = ascGen.with_value(|g| g.fontNames());
+
+    let onFontChange = move |e: Event| {
+        let f = event_target_value(&e);
+        logging::log!("Switching font to {}...", &f);
+        setFont(f);
+        // Chars that were valid may collapse to one intensity in the new font
+        if let RampSource::Custom(_) = rampSource.get_untracked() {
+            applyCustomChars(customChars.get_untracked());
+        }
+    };
+
+    let onCharsInput = move |e: Event| {
+        let chars = event_target_value(&e);
+        setCustomChars(chars.clone());
+        applyCustomChars(chars);
+    };
+
     view! {
         <aside class="w-80 h-auto bg-amber-50 overflow-y-auto">
             {displayImage}
             {displayInput}
+            <p class="px-8 pt-4 text-sm text-red-600">{openError}</p>
+            <Show when=dragging>
+                <div class="fixed inset-0 z-10 flex items-center justify-center border-4 border-dashed border-cyan-600 bg-cyan-600/10 pointer-events-none">
+                    <h4 class="text-xl font-medium text-zinc-800">Drop image to open</h4>
+                </div>
+            </Show>
             <div class="p-8 display-flex flex-col space-y-8">
-                // <Select name="font".to_string() label="Font".to_string()/>
-                <Slider
-                    name="contrast".to_string()
-                    label="Contrast".to_string()
-                    min=-100
-                    max=100
-                    value=0
-                    onInput=onContrastChange
+                <TextField
+                    name="imageLink".to_string()
+                    label="Image URL".to_string()
+                    value=imageLink
+                    error=linkError
+                    onInput=onImageLinkInput
                 />
-                <Slider
-                    name="brightness".to_string()
-                    label="Brightness".to_string()
-                    min=-100
-                    max=100
-                    value=0
-                    onInput=onBrightnessChange
+                <button
+                    class="w-full p-2 rounded-sm border border-amber-500 text-sm font-medium text-zinc-800 hover:bg-amber-100"
+                    on:click=onLoadLink
+                >
+                    Load from URL
+                </button>
+                <Select
+                    name="font".to_string()
+                    label="Font".to_string()
+                    options=fontOptions
+                    value=font
+                    onChange=onFontChange
+                />
+                <Select
+                    name="ramp".to_string()
+                    label="Ramp".to_string()
+                    options=rampOptions
+                    value=selectedRamp
+                    onChange=onRampChange
+                />
+                <TextField
+                    name="chars".to_string()
+                    label="Chars".to_string()
+                    value=customChars
+                    error=charsetError
+                    onInput=onCharsInput
                 />
                 <Slider
-                    name="size".to_string()
+                    name="rampLength".to_string()
+                    label="Ramp length".to_string()
+                    min=2
+                    max=40
+                    value=12
+                    onInput=onRampLengthChange
+                />
+                <button
+                    class="w-full p-2 rounded-sm border border-amber-500 text-sm font-medium text-zinc-800 hover:bg-amber-100"
+                    on:click=onGenerateRamp
+                >
+                    Generate optimal ramp
+                </button>
+                <Select
+                    name="sizeMode".to_string()
                     label="Size".to_string()
-                    min=10
-                    max=300
-                    value=100
-                    onInput=onSizeChange
+                    options=sizeModes
+                    value=sizeMode
+                    onChange=onSizeModeChange
+                />
+                <Show when=move || sizeMode() != "rows">
+                    <Slider
+                        name="columns".to_string()
+                        label="Columns".to_string()
+                        min=10
+                        max=MAX_COLUMNS as i16
+                        value=Signal::derive(move || columns() as i16)
+                        onInput=onColumnsChange
+                    />
+                </Show>
+                <Show when=move || sizeMode() != "columns">
+                    <Slider
+                        name="rows".to_string()
+                        label="Rows".to_string()
+                        min=5
+                        max=MAX_ROWS as i16
+                        value=Signal::derive(move || rows() as i16)
+                        onInput=onRowsChange
+                    />
+                </Show>
+                <Show when=move || sizeMode() == "box">
+                    <Select
+                        name="fit".to_string()
+                        label="Fit".to_string()
+                        options=Fit::options()
+                        value=selectedFit
+                        onChange=onFitChange
+                    />
+                </Show>
+                <p class="text-sm text-zinc-800">{gridLabel}</p>
+                <Adjustments pipeline setPipeline/>
+                <Select
+                    name="tone".to_string()
+                    label="Tone".to_string()
+                    options=Tone::options()
+                    value=selectedTone
+                    onChange=onToneChange
+                />
+                <Select
+                    name="transparency".to_string()
+                    label="Transparency".to_string()
+                    options=transparencyOptions
+                    value=selectedTransparency
+                    onChange=onTransparencyChange
                 />
+                <Show when=move || transparency() != Transparency::Blank>
+                    <input
+                        type="color"
+                        class="w-full h-8 rounded-sm border border-amber-500 bg-transparent"
+                        value=move || toHexColor(fillColor())
+                        on:input=onFillColorInput
+                    />
+                </Show>
                 <Toggle
                     name="dither".to_string()
                     label="Dither".to_string()
+                    checked=dither
                     onInput=onDitherChange
                 />
+                <Toggle
+                    name="keepSettings".to_string()
+                    label="Keep settings for new images".to_string()
+                    checked=keepSettings
+                    onInput=onKeepSettingsChange
+                />
+                <BannerText setBanner/>
+                <Layers composition setComposition selected setSelected/>
+                <Presets settings applySettings/>
             </div>
         </aside>
     }
 }
+
+async fn fetchImage(link: &str) -> Result<Vec<u8>, String> {
+    // Hosts that don't send CORS headers fail here without saying why
+    let blocked = |_| "Unable to load the image, the site may not allow it".to_string();
+    let response: Response = JsFuture::from(window().fetch_with_str(link))
+        .await
+        .map_err(blocked)?
+        .unchecked_into();
+    if !response.ok() {
+        return Err(format!(
+            "Loading the image failed with status {}",
+            response.status()
+        ));
+    }
+    // A declared size is checked before the body is read, so a huge file stays out of memory
+    let declared = response
+        .headers()
+        .get("content-length")
+        .ok()
+        .flatten()
+        .and_then(|len| len.parse::<usize>().ok());
+    if let Some(size) = declared.filter(|&size| size > MAX_FILE_BYTES) {
+        return Err(LoadError::TooLarge(size).to_string());
+    }
+    let buffer: ArrayBuffer = JsFuture::from(response.array_buffer().map_err(blocked)?)
+        .await
+        .map_err(blocked)?
+        .unchecked_into();
+    // Sites that don't send a length are checked once the body is in
+    let size = buffer.byte_length() as usize;
+    if size > MAX_FILE_BYTES {
+        return Err(LoadError::TooLarge(size).to_string());
+    }
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
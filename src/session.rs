@@ -0,0 +1,159 @@
+use leptos::logging;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Function, Promise, Uint8Array},
+    IdbDatabase, IdbRequest, IdbTransactionMode,
+};
+
+use crate::{
+    asciiGenerator::AsciiGenerator, imageHandler::MAX_FILE_BYTES, settings::ConversionSettings,
+};
+
+const SETTINGS_KEY: &str = "rusciii.session";
+const KEEP_SETTINGS_KEY: &str = "rusciii.keepSettings";
+const DB_NAME: &str = "rusciii";
+const STORE: &str = "session";
+const IMAGE_KEY: &str = "image";
+// Same limit the upload enforces, anything bigger isn't worth the quota
+pub const MAX_STORED_IMAGE_BYTES: usize = MAX_FILE_BYTES;
+
+pub fn loadSettings(ascGen: &AsciiGenerator) -> Option<ConversionSettings> {
+    // Settings a later build can't use are thrown away, or every load would fail on them
+    let storage = leptos::window().local_storage().ok().flatten()?;
+    let json = storage.get_item(SETTINGS_KEY).ok().flatten()?;
+    let restored = serde_json::from_str::<ConversionSettings>(&json)
+        .map_err(|e| e.to_string())
+        .and_then(|s| s.validate(ascGen));
+    match restored {
+        Ok(settings) => Some(settings),
+        Err(err) => {
+            logging::warn!("Discarding saved session settings: {err}");
+            storage.remove_item(SETTINGS_KEY).ok();
+            None
+        }
+    }
+}
+
+pub fn saveSettings(settings: &ConversionSettings) {
+    let json = serde_json::to_string(settings).expect("Settings should serialize");
+    if let Some(storage) = leptos::window().local_storage().ok().flatten() {
+        if storage.set_item(SETTINGS_KEY, &json).is_err() {
+            logging::warn!("Failed to save session settings, storage may be full");
+        }
+    }
+}
+
+pub fn loadKeepSettings() -> bool {
+    // A preference rather than session state, so starting over leaves it alone.
+    // On unless turned off, so link and session settings survive the next upload
+    leptos::window()
+        .local_storage()
+        .ok()
+        .flatten()
+        .and_then(|storage| storage.get_item(KEEP_SETTINGS_KEY).ok().flatten())
+        .is_none_or(|keep| keep == "true")
+}
+
+pub fn saveKeepSettings(keep: bool) {
+    if let Some(storage) = leptos::window().local_storage().ok().flatten() {
+        storage.set_item(KEEP_SETTINGS_KEY, &keep.to_string()).ok();
+    }
+}
+
+pub async fn loadImageBytes() -> Option<Vec<u8>> {
+    let stored = async {
+        let db = openDb().await?;
+        let store = db.transaction_with_str(STORE)?.object_store(STORE)?;
+        request(&store.get(&IMAGE_KEY.into())?).await
+    };
+    match stored.await {
+        Ok(bytes) if !bytes.is_undefined() => Some(Uint8Array::new(&bytes).to_vec()),
+        Ok(_) => None,
+        Err(err) => {
+            logging::warn!("Failed to restore image: {err:?}");
+            None
+        }
+    }
+}
+
+pub async fn saveImageBytes(bytes: Vec<u8>) {
+    // A previous image must not come back in place of one too big to keep
+    if bytes.len() > MAX_STORED_IMAGE_BYTES {
+        logging::warn!("Image is too big to keep across reloads");
+        return deleteImageBytes().await;
+    }
+    let stored = async {
+        let db = openDb().await?;
+        let store = db
+            .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?
+            .object_store(STORE)?;
+        let pixels = Uint8Array::from(bytes.as_slice());
+        request(&store.put_with_key(&pixels, &IMAGE_KEY.into())?).await
+    };
+    if let Err(err) = stored.await {
+        logging::warn!("Failed to save image: {err:?}");
+    }
+}
+
+pub async fn deleteImageBytes() {
+    let deleted = async {
+        let db = openDb().await?;
+        let store = db
+            .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?
+            .object_store(STORE)?;
+        request(&store.delete(&IMAGE_KEY.into())?).await
+    };
+    if let Err(err) = deleted.await {
+        logging::warn!("Failed to delete image: {err:?}");
+    }
+}
+
+pub async fn clear() {
+    if let Some(storage) = leptos::window().local_storage().ok().flatten() {
+        storage.remove_item(SETTINGS_KEY).ok();
+    }
+    deleteImageBytes().await;
+}
+
+async fn openDb() -> Result<IdbDatabase, JsValue> {
+    let factory = leptos::window()
+        .indexed_db()?
+        .ok_or("IndexedDB is unavailable")?;
+    let open = factory.open_with_u32(DB_NAME, 1)?;
+    let upgrading = open.clone();
+    let onUpgrade = Closure::once_into_js(move || {
+        let db: IdbDatabase = upgrading
+            .result()
+            .expect("Upgrade should have a database")
+            .unchecked_into();
+        db.create_object_store(STORE)
+            .expect("Failed to create session store");
+    });
+    open.set_onupgradeneeded(Some(onUpgrade.unchecked_ref()));
+    Ok(request(&open).await?.unchecked_into())
+}
+
+async fn request(req: &IdbRequest) -> Result<JsValue, JsValue> {
+    // IndexedDB predates promises, so each request gets wrapped in one
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let succeeded = req.clone();
+        let onSuccess = Closure::once_into_js(move || {
+            resolve
+                .call1(&JsValue::NULL, &succeeded.result().unwrap_or_default())
+                .ok();
+        });
+        let failed = req.clone();
+        let onError = Closure::once_into_js(move || {
+            let err = failed
+                .error()
+                .ok()
+                .flatten()
+                .map_or(JsValue::NULL, Into::into);
+            reject.call1(&JsValue::NULL, &err).ok();
+        });
+        req.set_onsuccess(Some(onSuccess.unchecked_ref()));
+        req.set_onerror(Some(onError.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
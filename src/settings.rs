@@ -0,0 +1,293 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    adjustments::{Adjustment, Pipeline},
+    asciiGenerator::AsciiGenerator,
+    imageHandler::{OutputSize, Tone, Transparency},
+    ramp::{Background, RampSource},
+};
+
+const PRESETS_KEY: &str = "rusciii.presets";
+const URL_KEY: &str = "settings=";
+
+/// Everything that decides how an image turns into art, apart from the image itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConversionSettings {
+    pub font: String,
+    pub rampSource: RampSource,
+    pub size: OutputSize,
+    pub pipeline: Pipeline,
+    pub tone: Tone,
+    pub dither: bool,
+    pub background: Background,
+    pub transparency: Transparency,
+}
+
+impl Default for ConversionSettings {
+    fn default() -> Self {
+        Self {
+            font: "courierPrime".to_string(),
+            rampSource: RampSource::default(),
+            size: OutputSize::default(),
+            pipeline: Pipeline::default(),
+            tone: Tone::default(),
+            dither: false,
+            background: Background::default(),
+            transparency: Transparency::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SettingsFormat {
+    Json,
+    Toml,
+}
+
+impl SettingsFormat {
+    pub fn options() -> Vec<(String, String)> {
+        vec![
+            ("json".to_string(), "JSON".to_string()),
+            ("toml".to_string(), "TOML".to_string()),
+        ]
+    }
+
+    pub fn fromId(id: &str) -> Option<Self> {
+        match id {
+            "json" => Some(SettingsFormat::Json),
+            "toml" => Some(SettingsFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            SettingsFormat::Json => "json",
+            SettingsFormat::Toml => "toml",
+        }
+    }
+
+    pub fn fromPath(path: &str) -> Option<Self> {
+        let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        Self::fromId(&ext)
+    }
+}
+
+impl ConversionSettings {
+    pub fn export(&self, format: SettingsFormat) -> String {
+        match format {
+            SettingsFormat::Json => {
+                serde_json::to_string_pretty(self).expect("Settings should serialize to json")
+            }
+            SettingsFormat::Toml => {
+                toml::to_string_pretty(self).expect("Settings should serialize to toml")
+            }
+        }
+    }
+
+    pub fn import(
+        text: &str,
+        format: SettingsFormat,
+        ascGen: &AsciiGenerator,
+    ) -> Result<Self, String> {
+        // Fields missing from the text keep their defaults
+        let settings: Self = match format {
+            SettingsFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string())?,
+            SettingsFormat::Toml => toml::from_str(text).map_err(|e| e.to_string())?,
+        };
+        settings.validate(ascGen)
+    }
+
+    pub fn validate(mut self, ascGen: &AsciiGenerator) -> Result<Self, String> {
+        // Links, files and storage can name fonts or presets this build doesn't have
+        if !ascGen.fontNames().iter().any(|(id, _)| *id == self.font) {
+            return Err(format!("Unknown font {}", self.font));
+        }
+        match &self.rampSource {
+            RampSource::Preset(id) if !ascGen.presets().iter().any(|p| &p.id == id) => {
+                return Err(format!("Unknown ramp preset {id}"));
+            }
+            RampSource::Custom(chars) => ascGen
+                .validateCharset(&self.font, chars)
+                .map_err(|e| format!("Invalid chars: {e}"))?,
+            RampSource::Preset(_) => {}
+        }
+        self.size = self.size.clamped();
+        self.pipeline.steps = self
+            .pipeline
+            .steps
+            .into_iter()
+            .map(Adjustment::clamped)
+            .collect();
+        Ok(self)
+    }
+
+    pub fn toUrlParam(&self) -> String {
+        let json = serde_json::to_string(self).expect("Settings should serialize to json");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn fromUrlParam(param: &str) -> Result<Self, String> {
+        let json = URL_SAFE_NO_PAD.decode(param).map_err(|e| e.to_string())?;
+        serde_json::from_slice(&json).map_err(|e| e.to_string())
+    }
+}
+
+pub fn readUrlSettings(ascGen: &AsciiGenerator) -> Option<Result<ConversionSettings, String>> {
+    // Settings ride in the hash so they never reach a server, None when there are none
+    let hash = leptos::window().location().hash().ok()?;
+    let param = hash.trim_start_matches('#').strip_prefix(URL_KEY)?;
+    Some(ConversionSettings::fromUrlParam(param).and_then(|s| s.validate(ascGen)))
+}
+
+pub fn writeUrlSettings(settings: &ConversionSettings) {
+    // Replaced rather than pushed so every slider step doesn't add a history entry
+    let url = format!("#{URL_KEY}{}", settings.toUrlParam());
+    let history = leptos::window()
+        .history()
+        .expect("Window should have history");
+    if history
+        .replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+        .is_err()
+    {
+        leptos::logging::warn!("Failed to update settings in url");
+    }
+}
+
+pub fn loadPresets(ascGen: &AsciiGenerator) -> BTreeMap<String, ConversionSettings> {
+    // Presets that no longer parse or validate are dropped rather than breaking the page
+    let Some(storage) = leptos::window().local_storage().ok().flatten() else {
+        return BTreeMap::new();
+    };
+    let presets: BTreeMap<String, ConversionSettings> = storage
+        .get_item(PRESETS_KEY)
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    presets
+        .into_iter()
+        .filter_map(|(name, preset)| match preset.validate(ascGen) {
+            Ok(preset) => Some((name, preset)),
+            Err(err) => {
+                leptos::logging::warn!("Dropping preset {name}: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn savePresets(presets: &BTreeMap<String, ConversionSettings>) {
+    let json = serde_json::to_string(presets).expect("Presets should serialize");
+    if let Some(storage) = leptos::window().local_storage().ok().flatten() {
+        if storage.set_item(PRESETS_KEY, &json).is_err() {
+            leptos::logging::warn!("Failed to save presets, storage may be full");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imageHandler::{Fit, MAX_COLUMNS};
+
+    fn custom() -> ConversionSettings {
+        let mut settings = ConversionSettings {
+            font: "menlo".to_string(),
+            rampSource: RampSource::Custom("@%=-. ".to_string()),
+            size: OutputSize::Box {
+                columns: 80,
+                rows: 24,
+                fit: Fit::Cover,
+            },
+            tone: Tone::Clahe {
+                tiles: 8,
+                clipLimit: 3.0,
+            },
+            dither: true,
+            background: Background::Dark,
+            transparency: Transparency::Blank,
+            ..ConversionSettings::default()
+        };
+        settings
+            .pipeline
+            .steps
+            .push(Adjustment::Rotate { quarterTurns: 1 });
+        settings
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let ascGen = AsciiGenerator::new();
+        for format in [SettingsFormat::Json, SettingsFormat::Toml] {
+            let text = custom().export(format);
+            assert_eq!(
+                ConversionSettings::import(&text, format, &ascGen),
+                Ok(custom()),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_fields_default() {
+        let ascGen = AsciiGenerator::new();
+        let import = |text, format| ConversionSettings::import(text, format, &ascGen);
+        let settings = import("dither = true", SettingsFormat::Toml).unwrap();
+        assert!(settings.dither);
+        assert_eq!(settings.font, ConversionSettings::default().font);
+        assert!(import("{", SettingsFormat::Json).is_err());
+        assert!(import("font = \"comicSans\"", SettingsFormat::Toml).is_err());
+        assert_eq!(
+            SettingsFormat::fromPath("look.TOML"),
+            Some(SettingsFormat::Toml)
+        );
+    }
+
+    #[test]
+    fn test_url_param_round_trips() {
+        let param = custom().toUrlParam();
+        assert!(param
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(ConversionSettings::fromUrlParam(&param), Ok(custom()));
+        assert!(ConversionSettings::fromUrlParam("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let ascGen = AsciiGenerator::new();
+        assert_eq!(custom().validate(&ascGen), Ok(custom()));
+        let check = |f: fn(&mut ConversionSettings)| {
+            let mut settings = custom();
+            f(&mut settings);
+            settings.validate(&ascGen)
+        };
+        assert!(check(|s| s.font = "comicSans".to_string()).is_err());
+        assert!(check(|s| s.rampSource = RampSource::Preset("nope".to_string())).is_err());
+        assert!(
+            check(|s| s.rampSource = RampSource::Custom("\u{2588}\u{2591}".to_string())).is_err()
+        );
+        let huge = check(|s| s.size = OutputSize::Columns(u32::MAX)).unwrap();
+        assert_eq!(huge.size, OutputSize::Columns(MAX_COLUMNS));
+        let steps = check(|s| {
+            s.pipeline.steps = vec![
+                Adjustment::Median { radius: 1_000_000 },
+                Adjustment::Blur { sigma: 1e30 },
+            ]
+        })
+        .unwrap()
+        .pipeline
+        .steps;
+        assert_eq!(
+            steps,
+            [
+                Adjustment::Median { radius: 5 },
+                Adjustment::Blur { sigma: 10.0 }
+            ]
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, rc::Rc};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{
+    js_sys::{self, Array, Uint8Array},
+    DedicatedWorkerGlobalScope, ErrorEvent, MessageEvent, Worker,
+};
+
+use crate::{
+    adjustments::{Adjustment, Pipeline},
+    asciiArt::AsciiArt,
+    asciiGenerator::{blankTransparent, AsciiGenerator},
+    imageHandler::{ImageHandler, OutputSize, Tone},
+    ramp::{Background, RampSource},
+    settings::ConversionSettings,
+};
+
+// Script trunk generates to boot the worker bin
+const WORKER_SCRIPT: &str = "./worker_loader.js";
+// A worker that keeps dying, or never loads, isn't restarted forever
+const MAX_RESTARTS: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// Followed by the gray pixels and, for transparent images, the alpha mask
+    SetImage {
+        width: u32,
+        height: u32,
+    },
+    ClearImage,
+    Convert {
+        id: u32,
+        job: ConversionSettings,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Reply {
+    Rendered(Rendered),
+    /// A request the worker couldn't act on, with the job's id when it got that far
+    Failed {
+        id: Option<u32>,
+        error: String,
+    },
+}
+
+/// Why the preview didn't update, a crash also loses the image the worker held.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkerFailure {
+    Job(String),
+    Crashed(String),
+    Stopped(String),
+}
+
+impl std::fmt::Display for WorkerFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkerFailure::Job(err) => write!(f, "Conversion failed: {err}"),
+            WorkerFailure::Crashed(err) => write!(f, "Conversion crashed and restarted: {err}"),
+            WorkerFailure::Stopped(err) => write!(f, "Conversion stopped after crashing: {err}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rendered {
+    pub id: u32,
+    pub art: String,
+    pub columns: u32,
+    pub rows: u32,
+    pub timings: Vec<StageTiming>,
+}
+
+/// How long a stage took for the last job, or that its cached output was reused.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub millis: f64,
+    pub cached: bool,
+}
+
+struct Stage<K, V> {
+    name: &'static str,
+    cache: Option<(K, V)>,
+}
+
+impl<K: PartialEq, V> Stage<K, V> {
+    fn new(name: &'static str) -> Self {
+        Self { name, cache: None }
+    }
+
+    fn run(
+        &mut self,
+        key: K,
+        dirty: &mut bool,
+        timings: &mut Vec<StageTiming>,
+        compute: impl FnOnce() -> V,
+    ) -> &V {
+        // Reruns when its inputs changed or an earlier stage produced new output
+        let cached = !*dirty && self.cache.as_ref().is_some_and(|(k, _)| *k == key);
+        let start = now();
+        if !cached {
+            self.cache = Some((key, compute()));
+            *dirty = true;
+        }
+        timings.push(StageTiming {
+            stage: self.name.to_string(),
+            millis: now() - start,
+            cached,
+        });
+        &self.cache.as_ref().expect("Stage should have output").1
+    }
+}
+
+type ConvertKey = (String, RampSource, Background, bool);
+
+/// Source image plus the output of every stage of the last job, so a change
+/// only reruns the stages after it.
+pub struct Stages {
+    gray: GrayImage,
+    alpha: Option<GrayImage>,
+    orient: Stage<Vec<Adjustment>, (GrayImage, Option<GrayImage>)>,
+    resize: Stage<OutputSize, (GrayImage, Option<GrayImage>)>,
+    filter: Stage<(Vec<Adjustment>, Tone), GrayImage>,
+    convert: Stage<ConvertKey, AsciiArt>,
+}
+
+impl Stages {
+    pub fn new(gray: GrayImage, alpha: Option<GrayImage>) -> Self {
+        Self {
+            gray,
+            alpha,
+            orient: Stage::new("Orient"),
+            resize: Stage::new("Resize"),
+            filter: Stage::new("Filter"),
+            convert: Stage::new("Convert"),
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        ascGen: &AsciiGenerator,
+        job: &ConversionSettings,
+    ) -> (&AsciiArt, Vec<StageTiming>) {
+        let mut timings = vec![];
+        let mut dirty = false;
+        let geometry = job.pipeline.geometry();
+        let (gray, alpha) = (&self.gray, &self.alpha);
+        let oriented = self
+            .orient
+            .run(geometry.clone(), &mut dirty, &mut timings, || {
+                let orient = |img: &GrayImage| {
+                    geometry
+                        .iter()
+                        .fold(img.clone(), |img, step| step.apply(img))
+                };
+                (orient(gray), alpha.as_ref().map(orient))
+            });
+        let resized = self.resize.run(job.size, &mut dirty, &mut timings, || {
+            // The mask follows the image into cells so they line up
+            let (img, mask) = oriented;
+            (
+                img.resizeTo(job.size),
+                mask.as_ref().map(|m| m.resizeTo(job.size).downsample()),
+            )
+        });
+        let filters = job.pipeline.filters();
+        let cells = self.filter.run(
+            (filters.clone(), job.tone),
+            &mut dirty,
+            &mut timings,
+            || {
+                filters
+                    .iter()
+                    .fold(resized.0.clone(), |img, step| step.apply(img))
+                    .downsample()
+                    .tone(job.tone)
+            },
+        );
+        let key = (
+            job.font.clone(),
+            job.rampSource.clone(),
+            job.background,
+            job.dither,
+        );
+        let art = self.convert.run(key, &mut dirty, &mut timings, || {
+            let ramp = ascGen
+                .ramp(&job.font, &job.rampSource)
+                .forBackground(job.background);
+            let mut art = match job.dither {
+                true => ascGen.convertWithDither(&ramp, cells),
+                false => ascGen.convert(&ramp, cells),
+            };
+            if let Some(mask) = &resized.1 {
+                blankTransparent(&mut art, mask);
+            }
+            art
+        });
+        (art, timings)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> f64 {
+    let since = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Clock should be past the epoch");
+    since.as_secs_f64() * 1000.0
+}
+
+pub fn serve() {
+    // Entry point of the worker bin, converts whatever the main thread sends
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let replyScope = scope.clone();
+    let ascGen = AsciiGenerator::new();
+    let mut stages: Option<Stages> = None;
+
+    let reply = move |reply: &Reply| {
+        let json = serde_json::to_string(reply).expect("Reply should serialize");
+        replyScope
+            .post_message(&json.into())
+            .expect("Failed to send reply");
+    };
+
+    // Bad requests are answered rather than panicking, which would take the worker down
+    let onMessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+        let parts: Array = e.data().unchecked_into();
+        let request = parts
+            .get(0)
+            .as_string()
+            .ok_or_else(|| "request isn't json".to_string())
+            .and_then(|header| serde_json::from_str(&header).map_err(|e| e.to_string()));
+        let request = match request {
+            Ok(request) => request,
+            Err(error) => return reply(&Reply::Failed { id: None, error }),
+        };
+        match request {
+            Request::SetImage { width, height } => {
+                let layer = |i: u32| {
+                    let data = parts.get(i);
+                    if data.is_undefined() {
+                        return None;
+                    }
+                    let layer = GrayImage::from_raw(width, height, Uint8Array::new(&data).to_vec());
+                    if layer.is_none() {
+                        leptos::logging::warn!("Dropping image, pixels don't match its size");
+                    }
+                    layer
+                };
+                stages = layer(1).map(|gray| Stages::new(gray, layer(2)));
+            }
+            Request::ClearImage => stages = None,
+            Request::Convert { id, job } => {
+                let job = match job.validate(&ascGen) {
+                    Ok(job) => job,
+                    Err(error) => {
+                        return reply(&Reply::Failed {
+                            id: Some(id),
+                            error,
+                        })
+                    }
+                };
+                let rendered = match stages.as_mut() {
+                    Some(stages) => {
+                        let (art, timings) = stages.render(&ascGen, &job);
+                        Rendered {
+                            id,
+                            art: art.to_string(),
+                            columns: art.width() as u32,
+                            rows: art.height() as u32,
+                            timings,
+                        }
+                    }
+                    None => Rendered {
+                        id,
+                        ..Rendered::default()
+                    },
+                };
+                reply(&Reply::Rendered(rendered));
+            }
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    scope.set_onmessage(Some(onMessage.as_ref().unchecked_ref()));
+    onMessage.forget();
+}
+
+#[derive(Default)]
+struct Jobs {
+    latest: u32,
+    running: bool,
+    // Only the newest job waits, older ones are stale by the time the worker is free
+    queued: Option<(u32, ConversionSettings)>,
+    restarts: u32,
+}
+
+type OnRendered = Rc<dyn Fn(Rendered)>;
+type OnFailed = Rc<dyn Fn(WorkerFailure)>;
+
+/// Main thread handle on the conversion worker.
+#[derive(Clone)]
+pub struct ConversionWorker {
+    worker: Rc<RefCell<Worker>>,
+    jobs: Rc<RefCell<Jobs>>,
+}
+
+impl ConversionWorker {
+    pub fn spawn(
+        onRendered: impl Fn(Rendered) + 'static,
+        onFailed: impl Fn(WorkerFailure) + 'static,
+    ) -> Self {
+        let worker = Worker::new(WORKER_SCRIPT).expect("Failed to start conversion worker");
+        let this = Self {
+            worker: Rc::new(RefCell::new(worker)),
+            jobs: Rc::new(RefCell::new(Jobs::default())),
+        };
+        this.listen(Rc::new(onRendered), Rc::new(onFailed));
+        this
+    }
+
+    fn listen(&self, onRendered: OnRendered, onFailed: OnFailed) {
+        let client = self.clone();
+        let (rendered, failed) = (onRendered.clone(), onFailed.clone());
+        let onMessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let reply = e.data().as_string().expect("Reply should be json");
+            let reply: Reply = serde_json::from_str(&reply).expect("Failed to parse reply");
+            let next = {
+                let mut jobs = client.jobs.borrow_mut();
+                jobs.running = false;
+                jobs.queued.take()
+            };
+            if let Some((id, job)) = next {
+                client.post(id, &job);
+            }
+            // A newer job is on its way, so this reply is already out of date
+            let latest = client.jobs.borrow().latest;
+            match reply {
+                Reply::Rendered(r) if r.id == latest => rendered(r),
+                Reply::Failed { id, error } if id.is_none_or(|id| id == latest) => {
+                    failed(WorkerFailure::Job(error))
+                }
+                _ => {}
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+
+        let client = self.clone();
+        let onError = Closure::once(move |e: ErrorEvent| {
+            // A panic leaves the worker unusable, so a fresh one takes over. Jobs
+            // waiting on the old one are dropped, the new one has no image yet
+            e.prevent_default();
+            let (latest, restarts) = {
+                let jobs = client.jobs.borrow();
+                (jobs.latest, jobs.restarts + 1)
+            };
+            *client.jobs.borrow_mut() = Jobs {
+                latest,
+                restarts,
+                ..Jobs::default()
+            };
+            if restarts > MAX_RESTARTS {
+                return onFailed(WorkerFailure::Stopped(e.message()));
+            }
+            let fresh = Worker::new(WORKER_SCRIPT).expect("Failed to restart conversion worker");
+            client.worker.replace(fresh).terminate();
+            client.listen(onRendered, onFailed.clone());
+            onFailed(WorkerFailure::Crashed(e.message()));
+        });
+
+        let worker = self.worker.borrow();
+        worker.set_onmessage(Some(onMessage.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(onError.as_ref().unchecked_ref()));
+        onMessage.forget();
+        onError.forget();
+    }
+
+    pub fn setImage(&self, gray: Option<&GrayImage>, alpha: Option<&GrayImage>) {
+        let parts = Array::new();
+        let transfer = Array::new();
+        let request = match gray {
+            Some(gray) => Request::SetImage {
+                width: gray.width(),
+                height: gray.height(),
+            },
+            None => Request::ClearImage,
+        };
+        let header = serde_json::to_string(&request).expect("Request should serialize");
+        parts.push(&header.into());
+        for layer in gray.into_iter().chain(alpha) {
+            let pixels = Uint8Array::from(layer.as_raw().as_slice());
+            transfer.push(&pixels.buffer());
+            parts.push(&pixels);
+        }
+        self.worker
+            .borrow()
+            .post_message_with_transfer(&parts, &transfer)
+            .expect("Failed to send image to worker");
+    }
+
+    pub fn submit(&self, job: ConversionSettings) {
+        let (id, running) = {
+            let mut jobs = self.jobs.borrow_mut();
+            jobs.latest += 1;
+            (jobs.latest, jobs.running)
+        };
+        if running {
+            self.jobs.borrow_mut().queued = Some((id, job));
+        } else {
+            self.post(id, &job);
+        }
+    }
+
+    fn post(&self, id: u32, job: &ConversionSettings) {
+        self.jobs.borrow_mut().running = true;
+        let request = Request::Convert {
+            id,
+            job: job.clone(),
+        };
+        let header = serde_json::to_string(&request).expect("Request should serialize");
+        self.worker
+            .borrow()
+            .post_message(&Array::of1(&header.into()))
+            .expect("Failed to send job to worker");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn job() -> ConversionSettings {
+        ConversionSettings {
+            size: OutputSize::Columns(20),
+            font: "menlo".to_string(),
+            ..ConversionSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_render_blanks_transparent_cells() {
+        let ascGen = AsciiGenerator::new();
+        let gray = GrayImage::from_fn(140, 70, |x, _| Luma([(x * 255 / 139) as u8]));
+        let alpha = GrayImage::from_fn(140, 70, |x, _| Luma([if x < 70 { 0 } else { 255 }]));
+        let mut opaque = Stages::new(gray.clone(), None);
+        let mut stages = Stages::new(gray, Some(alpha));
+        let (art, _) = stages.render(&ascGen, &job());
+        assert_eq!((art.width(), art.height()), (20, 5));
+        assert!(art.slice(0, 0, 10, 5).glyphs().all(|c| c == ' '));
+        let opaqueArt = opaque.render(&ascGen, &job()).0;
+        assert_eq!(art.slice(10, 0, 10, 5), opaqueArt.slice(10, 0, 10, 5));
+    }
+
+    #[test]
+    fn test_only_later_stages_rerun() {
+        let ascGen = AsciiGenerator::new();
+        let mut stages = Stages::new(GrayImage::from_pixel(140, 70, Luma([90])), None);
+        let cached =
+            |timings: Vec<StageTiming>| -> Vec<bool> { timings.iter().map(|t| t.cached).collect() };
+        assert_eq!(cached(stages.render(&ascGen, &job()).1), [false; 4]);
+        assert_eq!(cached(stages.render(&ascGen, &job()).1), [true; 4]);
+
+        let mut dithered = job();
+        dithered.dither = true;
+        let timings = stages.render(&ascGen, &dithered).1;
+        assert_eq!(cached(timings), [true, true, true, false]);
+
+        dithered.tone = Tone::Equalize;
+        let timings = stages.render(&ascGen, &dithered).1;
+        assert_eq!(cached(timings), [true, true, false, false]);
+    }
+}